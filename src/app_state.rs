@@ -1,40 +1,47 @@
+use crate::{lyrics::{Lyrics, LyricsOrigin}, player::metadata::TrackMetadata};
 
 #[derive(Debug, Clone)]
 pub enum AppState {
     Connecting,
+    /// No song is playing on the player yet
+    Connected {
+        player_name: String,
+    },
     FetchingLyrics {
-        song_name: String,
-        artist_name: String,
+        metadata: TrackMetadata,
     },
     LyricsFetched {
-        song_name: String,
-        artist_name: String,
-        lyrics: String,
+        metadata: TrackMetadata,
+        lyrics: Lyrics,
         cover_art: Option<Vec<u8>>,
+        provider: String,
+        origin: LyricsOrigin,
     },
-    // TODO: Handle those
-    #[allow(unused)]
     SongNotFound {
-        song_name: String,
-        artist_name: String,
+        metadata: TrackMetadata,
     },
-    #[allow(unused)]
     NetworkFailed,
 }
 
 impl AppState {
-    pub fn is_different(&self, new_song_name: &str, new_artist_name: &str) -> bool {
+    pub fn is_different(&self, new_metadata: &TrackMetadata) -> bool {
         match self {
             AppState::FetchingLyrics {
-                song_name,
-                artist_name,
+                metadata,
             }
             | AppState::LyricsFetched {
-                song_name,
-                artist_name,
+                metadata,
                 ..
-            } => song_name != new_song_name || artist_name != new_artist_name,
+            } => !metadata.is_same_track(new_metadata),
             _ => true
         }
     }
-}
+
+    /// Fetch results are only shown if they are for the song that is still being waited for
+    pub fn is_fetching(&self, fetched_metadata: &TrackMetadata) -> bool {
+        match self {
+            AppState::FetchingLyrics { metadata } => metadata.is_same_track(fetched_metadata),
+            _ => false,
+        }
+    }
+}
\ No newline at end of file
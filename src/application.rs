@@ -1,33 +1,45 @@
 use glib::{Continue};
 use gtk::{ApplicationWindow};
-use std::{time::Duration, rc::Rc};
-use crossbeam_channel::Receiver;
-use gtk::prelude::{GtkWindowExt, WidgetExt};
+use std::{time::Duration, rc::Rc, cell::Cell};
+use crossbeam_channel::{Receiver, Sender};
+use gtk::{gio, prelude::{ActionMapExt, DialogExt, GtkWindowExt, WidgetExt, WidgetExtManual}};
 
-use crate::{app_state::AppState, widgets::{HeaderBar, LyricsView}, ui::UIEvent};
+use crate::{app_state::AppState, lyrics::cache::CacheStats, widgets::{HeaderBar, LyricsView}, ui::{CacheCommand, UIEvent}, player::{metadata::TrackMetadata, position_clock::PositionClock, player_selector::PlayerSelection}};
 
 pub struct LyricsApplication {
     window: gtk::ApplicationWindow,
     headerbar: HeaderBar,
     lyrics_view: LyricsView,
     app_state: AppState,
+    position_clock: Rc<Cell<Option<PositionClock>>>,
 }
 
 impl LyricsApplication {
-    pub fn init(app: &adw::Application, ui_event_receiver: Rc<Receiver<UIEvent>>) {
+    pub fn init(
+        app: &adw::Application,
+        ui_event_receiver: Rc<Receiver<UIEvent>>,
+        player_selection_sender: Sender<PlayerSelection>,
+        player_selection: PlayerSelection,
+        cache_command_sender: Sender<CacheCommand>,
+    ) {
         let window = ApplicationWindow::new(app);
 
         window.present();
 
         let mut app = LyricsApplication {
             window: window.clone(),
-            headerbar: HeaderBar::new(window),
+            headerbar: HeaderBar::new(window, player_selection_sender, player_selection),
             lyrics_view: LyricsView::new(),
             app_state: AppState::Connecting,
+            position_clock: Rc::new(Cell::new(None)),
         };
 
         app.build_ui();
 
+        app.init_cache_actions(cache_command_sender);
+
+        app.init_position_ticker();
+
         app.init_ui_event_consumer(ui_event_receiver);
     }
 
@@ -43,57 +55,132 @@ impl LyricsApplication {
         self.window.set_child(Some(self.lyrics_view.as_widget()));
     }
 
+    /// Window actions of the cache entries in the headerbar menu
+    fn init_cache_actions(&self, cache_command_sender: Sender<CacheCommand>) {
+        let actions = [
+            (HeaderBar::REFETCH_SONG_ACTION, CacheCommand::RefetchCurrentSong),
+            (HeaderBar::CLEAR_CACHE_ACTION, CacheCommand::Clear),
+            (HeaderBar::CACHE_STATS_ACTION, CacheCommand::ShowStats),
+        ];
+
+        for (name, command) in actions {
+            let action = gio::SimpleAction::new(name, None);
+            let cache_command_sender = cache_command_sender.clone();
+            action.connect_activate(move |_, _| {
+                cache_command_sender.send(command).unwrap();
+            });
+            self.window.add_action(&action);
+        }
+    }
+
+    fn show_cache_stats(&self, stats: &CacheStats) {
+        let dialog = gtk::MessageDialog::builder()
+            .transient_for(&self.window)
+            .modal(true)
+            .buttons(gtk::ButtonsType::Close)
+            .text("Lyrics Cache")
+            .secondary_text(&stats.to_string())
+            .build();
+
+        dialog.connect_response(|dialog, _| dialog.close());
+        dialog.show();
+    }
+
+    /// Moves the lyrics along with the playback at every frame
+    fn init_position_ticker(&self) {
+        let position_clock = self.position_clock.clone();
+        let lyrics_view = self.lyrics_view.clone();
+
+        self.window.add_tick_callback(move |_, _| {
+            if let Some(position_clock) = position_clock.get() {
+                lyrics_view.set_position(position_clock.position());
+            }
+
+            Continue(true)
+        });
+    }
+
     // TODO: Make this not take self
     fn init_ui_event_consumer(mut self, ui_event_receiver: Rc<Receiver<UIEvent>>) {
         glib::timeout_add_local(Duration::from_millis(50), move || {
-            match ui_event_receiver.try_recv() {
-                Ok(ui_event) => {
-                    match ui_event {
-                        UIEvent::SongInformationFetched { 
-                            song_name, 
-                            artist_name, 
-                            lyrics, 
-                            cover_art 
-                        } => self.update_ui(AppState::LyricsFetched { 
-                            song_name, 
-                            artist_name, 
-                            lyrics, 
-                            cover_art: Some(cover_art) 
-                        }),
-                        UIEvent::FetchingLyrics { song_name, artist_name } => self.update_ui(
-                            AppState::FetchingLyrics { song_name, artist_name }
-                        )
+            // Events come in bursts, all of them are handled so that the view keeps up
+            while let Ok(ui_event) = ui_event_receiver.try_recv() {
+                match ui_event {
+                    UIEvent::SongInformationFetched { 
+                        metadata, 
+                        lyrics, 
+                        cover_art,
+                        provider,
+                        origin,
+                    } => self.update_ui_with_fetched(&metadata.clone(), AppState::LyricsFetched { 
+                        metadata, 
+                        lyrics, 
+                        // Cached songs may not have one
+                        cover_art: Some(cover_art).filter(|cover_art| !cover_art.is_empty()),
+                        provider,
+                        origin,
+                    }),
+                    UIEvent::FetchingLyrics { metadata } => self.update_ui(
+                        AppState::FetchingLyrics { metadata }
+                    ),
+                    UIEvent::SongNotFound { metadata } => self.update_ui_with_fetched(
+                        &metadata.clone(),
+                        AppState::SongNotFound { metadata }
+                    ),
+                    UIEvent::NetworkFailed { metadata } => self.update_ui_with_fetched(
+                        &metadata,
+                        AppState::NetworkFailed
+                    ),
+                    UIEvent::PositionChanged(position_clock) => 
+                        self.position_clock.set(Some(position_clock)),
+                    UIEvent::PlayersChanged(player_names) =>
+                        self.headerbar.set_players(&player_names),
+                    UIEvent::CacheStats(stats) => self.show_cache_stats(&stats),
+                    UIEvent::PlayerConnected { player_name } => self.update_ui(
+                        AppState::Connected { player_name }
+                    ),
+                    UIEvent::PlayerDisconnected => {
+                        self.position_clock.set(None);
+                        self.update_ui(AppState::Connecting)
                     }
                 }
-                Err(_) => (/* println!("update_listener: {:?}", e) */),
             }
 
             Continue(true)
         });
     }
 
+    /// Drops the results of fetches that were outpaced by a song change
+    fn update_ui_with_fetched(&mut self, metadata: &TrackMetadata, new_app_state: AppState) {
+        if self.app_state.is_fetching(metadata) {
+            self.update_ui(new_app_state);
+        } else {
+            println!("update_ui: Discarded stale result for {}", metadata.title);
+        }
+    }
+
     pub fn update_ui(&mut self, new_app_state: AppState) {
         match &new_app_state {
-            AppState::LyricsFetched { lyrics, cover_art, .. } => {
-                self.lyrics_view.song_data_retrieved(lyrics, cover_art.as_deref());
+            AppState::LyricsFetched { lyrics, cover_art, provider, origin, .. } => {
+                self.lyrics_view.song_data_retrieved(lyrics, cover_art.as_deref(), provider, *origin);
             }
             AppState::FetchingLyrics {
-                song_name,
-                artist_name,
+                metadata,
             } => {
-                let should_change = self.app_state.is_different(song_name, artist_name);
+                let should_change = self.app_state.is_different(metadata);
 
                 if should_change {
-                    self.lyrics_view.song_changed(song_name, artist_name);
+                    self.lyrics_view.song_changed(metadata);
                 }
             }
             AppState::NetworkFailed => self.lyrics_view.network_failed(),
             AppState::SongNotFound {
-                song_name, artist_name
+                metadata
             } => {
-                self.lyrics_view.song_not_found(song_name, artist_name);
+                self.lyrics_view.song_not_found(metadata);
             }
-            AppState::Connecting => (),
+            AppState::Connected { player_name } => self.lyrics_view.player_connected(player_name),
+            AppState::Connecting => self.lyrics_view.not_connected(),
         }
 
         self.app_state = new_app_state;
@@ -0,0 +1,37 @@
+use crate::lyrics::cache::{CacheLimits, LyricsCache};
+
+const CACHE_USAGE: &'static str = "Cache commands:
+  --cache-stats                   Show how much the lyrics cache uses
+  --clear-cache                   Remove every cached song
+  --purge-cache <artist> <title>  Remove the cached lyrics of a song";
+
+/// Runs the cache command in `args` and returns the exit code,
+/// `None` if there is no command and the app should start
+pub fn run_cache_command(args: &[String], cache_limits: CacheLimits) -> Option<i32> {
+    let args = args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>();
+
+    let result = match args.as_slice() {
+        ["--cache-stats"] => LyricsCache::open(cache_limits)
+            .and_then(|cache| cache.stats())
+            .map(|stats| println!("{}", stats)),
+        ["--clear-cache"] => LyricsCache::open(cache_limits)
+            .and_then(|cache| cache.clear())
+            .map(|_| println!("Cleared the lyrics cache")),
+        ["--purge-cache", artist, title] => LyricsCache::open(cache_limits)
+            .and_then(|cache| cache.purge(artist, title))
+            .map(|purged| println!("Removed {} cached versions of {} by {}", purged, title, artist)),
+        [command, ..] if command.ends_with("-cache") || *command == "--cache-stats" => {
+            eprintln!("{}", CACHE_USAGE);
+            return Some(2);
+        }
+        _ => return None,
+    };
+
+    match result {
+        Ok(()) => Some(0),
+        Err(error) => {
+            eprintln!("{}", error);
+            Some(1)
+        }
+    }
+}
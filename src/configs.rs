@@ -1,3 +1,65 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
-pub const NETWORK_TIMEOUT_DURATION: Duration = Duration::from_secs(30);
\ No newline at end of file
+use gio::prelude::SettingsExt;
+
+use crate::lyrics::{cache::CacheLimits, registry::{FetchStrategy, RegistryConfig}};
+
+pub const NETWORK_TIMEOUT_DURATION: Duration = Duration::from_secs(30);
+
+pub const SCHEMA_ID: &'static str = "com.github.onsah.SyncLyrics";
+pub const SELECTED_PLAYER_KEY: &'static str = "selected-player";
+pub const PROVIDER_PRIORITY_KEY: &'static str = "provider-priority";
+pub const PROVIDER_STRATEGY_KEY: &'static str = "provider-strategy";
+pub const GENIUS_BASE_URL_KEY: &'static str = "genius-base-url";
+pub const LRCLIB_BASE_URL_KEY: &'static str = "lrclib-base-url";
+pub const LYRICS_LIBRARY_DIRS_KEY: &'static str = "lyrics-library-dirs";
+pub const EXTERNAL_PROVIDERS_KEY: &'static str = "external-providers";
+pub const EXTERNAL_PROVIDER_TIMEOUT_KEY: &'static str = "external-provider-timeout";
+pub const CACHE_MAX_SIZE_KEY: &'static str = "cache-max-size";
+pub const CACHE_TTL_DAYS_KEY: &'static str = "cache-ttl-days";
+
+/// `None` if the schema is not installed, e.g. when running from `cargo run`
+pub fn settings() -> Option<gio::Settings> {
+    gio::SettingsSchemaSource::default()?.lookup(SCHEMA_ID, true)?;
+
+    Some(gio::Settings::new(SCHEMA_ID))
+}
+
+/// Limits from the settings, the defaults if the schema is not installed
+pub fn cache_limits() -> CacheLimits {
+    match settings() {
+        Some(settings) => CacheLimits {
+            max_size: settings.uint(CACHE_MAX_SIZE_KEY) as u64 * 1024 * 1024,
+            ttl: Duration::from_secs(settings.uint(CACHE_TTL_DAYS_KEY) as u64 * 24 * 60 * 60),
+        },
+        None => CacheLimits::default(),
+    }
+}
+
+/// Provider settings, the defaults if the schema is not installed
+pub fn registry_config() -> RegistryConfig {
+    match settings() {
+        Some(settings) => RegistryConfig {
+            priority: settings
+                .strv(PROVIDER_PRIORITY_KEY)
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            strategy: FetchStrategy::from_setting(&settings.string(PROVIDER_STRATEGY_KEY)),
+            genius_base_url: settings.string(GENIUS_BASE_URL_KEY).to_string(),
+            lrclib_base_url: settings.string(LRCLIB_BASE_URL_KEY).to_string(),
+            library_dirs: settings
+                .strv(LYRICS_LIBRARY_DIRS_KEY)
+                .iter()
+                .map(|dir| PathBuf::from(dir.as_str()))
+                .collect(),
+            external_commands: settings
+                .strv(EXTERNAL_PROVIDERS_KEY)
+                .iter()
+                .map(|command| PathBuf::from(command.as_str()))
+                .collect(),
+            external_timeout: Duration::from_secs(settings.uint(EXTERNAL_PROVIDER_TIMEOUT_KEY) as u64),
+        },
+        None => RegistryConfig::default(),
+    }
+}
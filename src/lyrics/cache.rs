@@ -0,0 +1,589 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::player::metadata::TrackMetadata;
+
+use super::{normalize, Lyrics, LyricsResponse};
+
+const CACHE_DIR_NAME: &'static str = "sync-lyrics";
+const ENTRIES_DIR_NAME: &'static str = "lyrics";
+const COVER_ARTS_DIR_NAME: &'static str = "covers";
+const ENTRY_EXTENSION: &'static str = "json";
+const COVER_ART_EXTENSION: &'static str = "cover";
+
+/// Fetched lyrics saved under the user's cache directory, one json file per song.
+/// Cover arts are kept separately so that songs of the same album share one.
+pub struct LyricsCache {
+    dir: PathBuf,
+    limits: CacheLimits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheLimits {
+    /// In bytes, least recently used songs are evicted above it
+    pub max_size: u64,
+    /// Songs older than this are fetched again when possible
+    pub ttl: Duration,
+}
+
+pub struct CachedLyrics {
+    pub response: LyricsResponse,
+    /// Older than the ttl, still usable if the lyrics can't be fetched again
+    pub is_stale: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CacheStats {
+    pub songs: usize,
+    pub cover_arts: usize,
+    /// In bytes
+    pub size: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("Cache io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Malformed cache entry: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CacheEntry {
+    /// The full key, file names are only its hash
+    key: String,
+    artist: String,
+    track: String,
+    provider: String,
+    source_url: Option<String>,
+    lyrics: Lyrics,
+    confidence: f32,
+    /// Key of the cover art file
+    cover_art: Option<String>,
+    /// Seconds since the unix epoch
+    fetched_at: u64,
+}
+
+/// An entry on disk, the modification time of its file is when it was last used
+struct StoredEntry {
+    path: PathBuf,
+    entry: CacheEntry,
+    size: u64,
+    last_used: SystemTime,
+}
+
+impl LyricsCache {
+    /// Opens the cache in `$XDG_CACHE_HOME/sync-lyrics`
+    pub fn open(limits: CacheLimits) -> Result<Self, CacheError> {
+        Self::new(glib::user_cache_dir().join(CACHE_DIR_NAME), limits)
+    }
+
+    pub fn new(dir: PathBuf, limits: CacheLimits) -> Result<Self, CacheError> {
+        let cache = LyricsCache { dir, limits };
+        cache.create_dirs()?;
+
+        Ok(cache)
+    }
+
+    pub fn get(&self, metadata: &TrackMetadata) -> Result<Option<CachedLyrics>, CacheError> {
+        let key = Self::key(metadata);
+        let entry_path = self.entry_path(&key);
+
+        let entry = match Self::read_entry(&entry_path)? {
+            // Another song with the same hash
+            Some(entry) if entry.key == key => entry,
+            _ => return Ok(None),
+        };
+
+        let cover_art = match &entry.cover_art {
+            Some(cover_art_key) => match fs::read(self.cover_art_path(cover_art_key)) {
+                Ok(cover_art) => cover_art,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(error) => return Err(error.into()),
+            },
+            None => Vec::new(),
+        };
+
+        fs::File::options()
+            .write(true)
+            .open(&entry_path)?
+            .set_modified(SystemTime::now())?;
+
+        let age = Duration::from_secs(Self::now().saturating_sub(entry.fetched_at));
+
+        Ok(Some(CachedLyrics {
+            is_stale: age >= self.limits.ttl,
+            response: LyricsResponse {
+                artist: entry.artist,
+                track: entry.track,
+                lyrics: entry.lyrics,
+                cover_art,
+                provider: entry.provider,
+                source_url: entry.source_url,
+                confidence: entry.confidence,
+            },
+        }))
+    }
+
+    pub fn insert(&self, metadata: &TrackMetadata, response: &LyricsResponse) -> Result<(), CacheError> {
+        let key = Self::key(metadata);
+
+        let cover_art = if response.cover_art.is_empty() {
+            None
+        } else {
+            let cover_art_key = Self::cover_art_key(metadata);
+            Self::write(&self.cover_art_path(&cover_art_key), &response.cover_art)?;
+            Some(cover_art_key)
+        };
+
+        let entry = serde_json::to_vec(&CacheEntry {
+            key: key.clone(),
+            artist: response.artist.clone(),
+            track: response.track.clone(),
+            provider: response.provider.clone(),
+            source_url: response.source_url.clone(),
+            lyrics: response.lyrics.clone(),
+            confidence: response.confidence,
+            cover_art,
+            fetched_at: Self::now(),
+        })?;
+
+        // The entry is written last so that it never points to a missing cover art
+        Self::write(&self.entry_path(&key), &entry)?;
+
+        self.evict()
+    }
+
+    /// Removes every cached version of the song, returns how many there were
+    pub fn purge(&self, artist: &str, title: &str) -> Result<usize, CacheError> {
+        let prefix = Self::song_key(artist, title);
+        let (purged, kept): (Vec<_>, Vec<_>) = self
+            .entries()?
+            .into_iter()
+            .partition(|stored| stored.entry.key.starts_with(&prefix));
+
+        for stored in &purged {
+            fs::remove_file(&stored.path)?;
+        }
+
+        self.remove_unused_cover_arts(&kept)?;
+
+        Ok(purged.len())
+    }
+
+    pub fn clear(&self) -> Result<(), CacheError> {
+        for dir_name in &[ENTRIES_DIR_NAME, COVER_ARTS_DIR_NAME] {
+            match fs::remove_dir_all(self.dir.join(dir_name)) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => (),
+            }
+        }
+
+        self.create_dirs()
+    }
+
+    pub fn stats(&self) -> Result<CacheStats, CacheError> {
+        let mut stats = CacheStats::default();
+
+        for stored in self.entries()? {
+            stats.songs += 1;
+            stats.size += stored.size;
+        }
+        for (_, size) in self.cover_arts()? {
+            stats.cover_arts += 1;
+            stats.size += size;
+        }
+
+        Ok(stats)
+    }
+
+    /// Removes the least recently used songs until the cache fits in `max_size`.
+    /// Entries are only read when the sizes of the files are above it.
+    fn evict(&self) -> Result<(), CacheError> {
+        let entries_size = Self::files(&self.dir.join(ENTRIES_DIR_NAME), ENTRY_EXTENSION)?
+            .iter()
+            .map(|(_, size)| size)
+            .sum::<u64>();
+        let cover_arts_size = self.cover_arts()?.values().sum::<u64>();
+        if entries_size + cover_arts_size <= self.limits.max_size {
+            return Ok(());
+        }
+
+        let mut entries = self.entries()?;
+        entries.sort_by_key(|stored| stored.last_used);
+        let cover_arts = self.cover_arts()?;
+
+        // Cover arts are only freed once no song uses them
+        let mut cover_art_users: HashMap<&str, usize> = HashMap::new();
+        for stored in &entries {
+            if let Some(cover_art_key) = &stored.entry.cover_art {
+                *cover_art_users.entry(cover_art_key.as_str()).or_default() += 1;
+            }
+        }
+
+        let cover_art_size = |cover_art_key: &str| {
+            cover_arts.get(&self.cover_art_path(cover_art_key)).copied().unwrap_or(0)
+        };
+
+        let mut size = entries.iter().map(|stored| stored.size).sum::<u64>()
+            + cover_art_users.keys().map(|cover_art_key| cover_art_size(cover_art_key)).sum::<u64>();
+
+        let mut evicted = 0;
+        for stored in &entries {
+            if size <= self.limits.max_size {
+                break;
+            }
+
+            fs::remove_file(&stored.path)?;
+            size -= stored.size;
+            evicted += 1;
+
+            if let Some(cover_art_key) = &stored.entry.cover_art {
+                let users = cover_art_users.entry(cover_art_key.as_str()).or_default();
+                *users = users.saturating_sub(1);
+                if *users == 0 {
+                    size = size.saturating_sub(cover_art_size(cover_art_key));
+                }
+            }
+        }
+
+        if evicted > 0 {
+            println!("cache: Evicted {} songs", evicted);
+        }
+        // Cover arts left behind by removed songs are counted by the file sizes too
+        self.remove_unused_cover_arts(&entries[evicted..])
+    }
+
+    /// Removes the cover arts that none of `entries`, the songs left in the cache, use
+    fn remove_unused_cover_arts(&self, entries: &[StoredEntry]) -> Result<(), CacheError> {
+        let used = entries
+            .iter()
+            .filter_map(|stored| stored.entry.cover_art.as_ref())
+            .map(|cover_art_key| self.cover_art_path(cover_art_key))
+            .collect::<Vec<_>>();
+
+        for (path, _) in self.cover_arts()? {
+            if !used.contains(&path) {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every readable entry, unreadable ones are removed
+    fn entries(&self) -> Result<Vec<StoredEntry>, CacheError> {
+        let mut entries = Vec::new();
+
+        for (path, size) in Self::files(&self.dir.join(ENTRIES_DIR_NAME), ENTRY_EXTENSION)? {
+            match Self::read_entry(&path) {
+                Ok(Some(entry)) => entries.push(StoredEntry {
+                    last_used: fs::metadata(&path)?.modified()?,
+                    path,
+                    entry,
+                    size,
+                }),
+                Ok(None) => (),
+                Err(error) => {
+                    println!("cache: Removing {:?}: {}", path, error);
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn cover_arts(&self) -> Result<HashMap<PathBuf, u64>, CacheError> {
+        Ok(Self::files(&self.dir.join(COVER_ARTS_DIR_NAME), COVER_ART_EXTENSION)?
+            .into_iter()
+            .collect())
+    }
+
+    /// Paths and sizes of the files in `dir` with `extension`
+    fn files(dir: &Path, extension: &str) -> Result<Vec<(PathBuf, u64)>, CacheError> {
+        let mut files = Vec::new();
+
+        for file in fs::read_dir(dir)? {
+            let file = file?;
+            let path = file.path();
+            if path.extension() == Some(extension.as_ref()) {
+                files.push((path, file.metadata()?.len()));
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn read_entry(path: &Path) -> Result<Option<CacheEntry>, CacheError> {
+        match fs::read(path) {
+            Ok(entry) => Ok(Some(serde_json::from_slice(&entry)?)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn create_dirs(&self) -> Result<(), CacheError> {
+        fs::create_dir_all(self.dir.join(ENTRIES_DIR_NAME))?;
+        fs::create_dir_all(self.dir.join(COVER_ARTS_DIR_NAME))?;
+
+        Ok(())
+    }
+
+    /// Normalized artist and title, the version and the track id if the player has one.
+    /// Live and remastered versions have other timings, so they are cached apart.
+    /// Players sharing a track id for every song still differ by the rest.
+    fn key(metadata: &TrackMetadata) -> String {
+        format!(
+            "{}{}\n{}",
+            Self::song_key(metadata.primary_artist().unwrap_or_default(), &metadata.title),
+            normalize::normalize(&metadata.title),
+            metadata.track_id.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Start of the keys of every version of a song
+    fn song_key(artist: &str, title: &str) -> String {
+        format!("{}\n{}\n", normalize::normalize_artist(artist), normalize::normalize_title(title))
+    }
+
+    /// Songs of the same album share the cover art, the song is used if the album is unknown
+    fn cover_art_key(metadata: &TrackMetadata) -> String {
+        match &metadata.album {
+            Some(album) => {
+                let artist = metadata
+                    .album_artists
+                    .first()
+                    .map(|artist| artist.as_str())
+                    .or_else(|| metadata.primary_artist())
+                    .unwrap_or_default();
+                format!("album\n{}\n{}", normalize::normalize_artist(artist), normalize::normalize_title(album))
+            }
+            None => format!("song\n{}", Self::key(metadata)),
+        }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        Self::path(&self.dir.join(ENTRIES_DIR_NAME), key, ENTRY_EXTENSION)
+    }
+
+    fn cover_art_path(&self, cover_art_key: &str) -> PathBuf {
+        Self::path(&self.dir.join(COVER_ARTS_DIR_NAME), cover_art_key, COVER_ART_EXTENSION)
+    }
+
+    fn path(dir: &Path, key: &str, extension: &str) -> PathBuf {
+        dir.join(format!("{:016x}.{}", Self::hash(key), extension))
+    }
+
+    /// FNV-1a, it has to stay the same between builds unlike `DefaultHasher`
+    fn hash(key: &str) -> u64 {
+        key.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs())
+    }
+
+    /// Writes to a temporary file first so that readers never see a partial file
+    fn write(path: &Path, contents: &[u8]) -> Result<(), CacheError> {
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)?;
+
+        Ok(())
+    }
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        CacheLimits {
+            max_size: 100 * 1024 * 1024,
+            ttl: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+impl Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} songs, {} cover arts, {:.1} MiB",
+            self.songs,
+            self.cover_arts,
+            self.size as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, time::{Duration, SystemTime}};
+
+    use crate::{lyrics::{Lyrics, LyricsResponse}, player::metadata::TrackMetadata};
+
+    use super::{CacheLimits, LyricsCache};
+
+    fn cache(name: &str, limits: CacheLimits) -> (LyricsCache, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("sync-lyrics-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        (LyricsCache::new(dir.clone(), limits).unwrap(), dir)
+    }
+
+    fn metadata(artist: &str, title: &str, track_id: Option<&str>) -> TrackMetadata {
+        TrackMetadata {
+            title: title.to_string(),
+            artists: vec![artist.to_string()],
+            track_id: track_id.map(|track_id| track_id.to_string()),
+            ..TrackMetadata::default()
+        }
+    }
+
+    fn album_track(title: &str) -> TrackMetadata {
+        TrackMetadata {
+            album: Some("Fear Inoculum".to_string()),
+            ..metadata("TOOL", title, None)
+        }
+    }
+
+    fn response(cover_art: Vec<u8>) -> LyricsResponse {
+        LyricsResponse {
+            artist: "TOOL".to_string(),
+            track: "Pneuma".to_string(),
+            lyrics: Lyrics::Plain("We are born of one breath".to_string()),
+            cover_art,
+            provider: "Genius".to_string(),
+            source_url: Some("https://genius.com/Tool-pneuma-lyrics".to_string()),
+            confidence: 1.0,
+        }
+    }
+
+    /// Makes the song look like it was last used `seconds_ago`
+    fn set_last_used(cache: &LyricsCache, track: &TrackMetadata, seconds_ago: u64) {
+        fs::File::options()
+            .write(true)
+            .open(cache.entry_path(&LyricsCache::key(track)))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(seconds_ago))
+            .unwrap();
+    }
+
+    #[test]
+    fn insert_and_get_works() {
+        let (cache, dir) = cache("insert", CacheLimits::default());
+        let track = metadata("TOOL", "Pneuma", Some("/com/spotify/track/1"));
+
+        assert!(cache.get(&track).unwrap().is_none());
+
+        cache.insert(&track, &response(vec![1, 2, 3])).unwrap();
+        let cached = cache.get(&track).unwrap().unwrap();
+
+        assert!(!cached.is_stale);
+        assert_eq!(cached.response.lyrics.to_plain(), "We are born of one breath");
+        assert_eq!(cached.response.cover_art, vec![1, 2, 3]);
+        assert_eq!(cached.response.provider, "Genius");
+        assert_eq!(cached.response.source_url.as_deref(), Some("https://genius.com/Tool-pneuma-lyrics"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn key_is_normalized() {
+        let (cache, dir) = cache("normalized", CacheLimits::default());
+
+        cache.insert(&metadata("TOOL", "Pneuma", None), &response(Vec::new())).unwrap();
+
+        let cached = cache.get(&metadata("tool", "  pneuma ", None)).unwrap().unwrap();
+        assert!(cached.response.cover_art.is_empty());
+        // Other versions may have other timings
+        assert!(cache.get(&metadata("TOOL", "Pneuma - Live", None)).unwrap().is_none());
+
+        // A shared track id doesn't make different songs the same
+        cache.insert(&metadata("TOOL", "Pneuma", Some("/org/mpris/MediaPlayer2/firefox")), &response(Vec::new())).unwrap();
+        assert!(cache.get(&metadata("TOOL", "Invincible", Some("/org/mpris/MediaPlayer2/firefox"))).unwrap().is_none());
+        assert!(cache.get(&metadata("TOOL", "Pneuma", Some("/com/spotify/track/1"))).unwrap().is_none());
+
+        // Purging removes every version
+        cache.insert(&metadata("TOOL", "Pneuma - Live", None), &response(Vec::new())).unwrap();
+        assert!(cache.get(&metadata("TOOL", "Pneuma (Live)", None)).unwrap().is_some());
+        assert_eq!(cache.purge("TOOL", "Pneuma").unwrap(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn expired_songs_are_stale() {
+        let (cache, dir) = cache("ttl", CacheLimits { ttl: Duration::from_secs(0), ..CacheLimits::default() });
+        let track = metadata("TOOL", "Pneuma", None);
+
+        cache.insert(&track, &response(Vec::new())).unwrap();
+
+        assert!(cache.get(&track).unwrap().unwrap().is_stale);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn least_recently_used_songs_are_evicted() {
+        let (cache, dir) = cache("evict", CacheLimits::default());
+        let tracks = ["Pneuma", "Invincible", "Descending"]
+            .iter()
+            .map(|title| metadata("TOOL", title, None))
+            .collect::<Vec<_>>();
+
+        for (i, track) in tracks.iter().enumerate() {
+            cache.insert(track, &response(Vec::new())).unwrap();
+            set_last_used(&cache, track, 100 - i as u64 * 10);
+        }
+        // Used most recently now
+        cache.get(&tracks[0]).unwrap();
+
+        // Just over the limit, one song has to go
+        let size = cache.stats().unwrap().size;
+        let cache = LyricsCache::new(dir.clone(), CacheLimits { max_size: size - 1, ..CacheLimits::default() }).unwrap();
+        cache.evict().unwrap();
+
+        assert!(cache.get(&tracks[0]).unwrap().is_some());
+        assert!(cache.get(&tracks[1]).unwrap().is_none());
+        assert!(cache.get(&tracks[2]).unwrap().is_some());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cover_art_is_shared_by_the_album() {
+        let (cache, dir) = cache("cover_art", CacheLimits::default());
+
+        cache.insert(&album_track("Pneuma"), &response(vec![1, 2, 3])).unwrap();
+        cache.insert(&album_track("Invincible"), &response(vec![1, 2, 3])).unwrap();
+        cache.insert(&metadata("TOOL", "Sober", None), &response(vec![4, 5, 6])).unwrap();
+
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.songs, stats.cover_arts), (3, 2));
+
+        assert_eq!(cache.purge("tool", "PNEUMA").unwrap(), 1);
+        assert_eq!(cache.get(&album_track("Invincible")).unwrap().unwrap().response.cover_art, vec![1, 2, 3]);
+
+        // The cover art goes away with its last song
+        assert_eq!(cache.purge("TOOL", "Invincible").unwrap(), 1);
+        assert_eq!(cache.stats().unwrap().cover_arts, 1);
+
+        cache.clear().unwrap();
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.songs, stats.cover_arts, stats.size), (0, 0, 0));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
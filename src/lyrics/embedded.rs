@@ -0,0 +1,445 @@
+use std::{
+    convert::TryInto,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use id3::frame::TimestampFormat;
+use thiserror::Error;
+
+use crate::player::metadata::TrackMetadata;
+
+use super::{
+    lrc::{SyncedLine, SyncedLyrics},
+    Lyrics, LyricsError, LyricsFetcher, LyricsResponse, LyricsResult,
+};
+
+static PROVIDER_NAME: &'static str = "Embedded tags";
+/// Vorbis comment fields with lyrics, compared ignoring case
+const VORBIS_LYRICS_FIELDS: [&'static str; 3] = ["LYRICS", "UNSYNCEDLYRICS", "UNSYNCED LYRICS"];
+const VORBIS_COMMENT_BLOCK: u8 = 4;
+/// Lyrics atom of MP4 files, `©lyr`
+const MP4_LYRICS_ATOM: [u8; 4] = *b"\xa9lyr";
+/// Path of the lyrics atom from the `moov` atom
+const MP4_LYRICS_PATH: [&[u8; 4]; 5] = [b"udta", b"meta", b"ilst", &MP4_LYRICS_ATOM, b"data"];
+
+/// Lyrics in the tags of local audio files:
+/// ID3 `SYLT` and `USLT` frames, Vorbis `LYRICS` comments of FLAC and Ogg files, MP4 `©lyr` atoms
+pub struct EmbeddedTags;
+
+#[derive(Debug, Error)]
+pub enum TagError {
+    #[error("Couldn't read the file: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid ID3 tag: {0}")]
+    Id3(#[from] id3::Error),
+    #[error("Invalid {0} file")]
+    Malformed(&'static str),
+}
+
+/// Audio file formats with lyrics tags, detected from the first bytes
+#[derive(Debug, PartialEq)]
+enum TagFormat {
+    Id3,
+    Flac,
+    Ogg,
+    Mp4,
+}
+
+impl EmbeddedTags {
+    fn read_lyrics(metadata: &TrackMetadata) -> LyricsResult {
+        let lyrics = match metadata.local_path() {
+            Some(path) => Self::read_file(&path).unwrap_or_else(|error| {
+                println!("embedded: Couldn't read the tags of {:?}: {}", path, error);
+                None
+            }),
+            None => None,
+        };
+
+        match lyrics {
+            Some(lyrics) => Ok(LyricsResponse {
+                artist: metadata.primary_artist().unwrap_or_default().to_string(),
+                track: metadata.title.clone(),
+                lyrics,
+                cover_art: Vec::new(),
+                provider: PROVIDER_NAME.to_string(),
+                source_url: metadata.url.clone(),
+                confidence: 1.0,
+            }),
+            None => Err(LyricsError::SongNotFound {
+                song_name: metadata.title.clone(),
+                artist: metadata.primary_artist().unwrap_or_default().to_string(),
+            }),
+        }
+    }
+
+    /// `None` if the file has no lyrics tags or isn't a supported format
+    pub fn read_file(path: &Path) -> Result<Option<Lyrics>, TagError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 12];
+        let read = reader.read(&mut magic)?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        match Self::detect_format(&magic[..read]) {
+            Some(TagFormat::Id3) => Self::read_id3(reader),
+            Some(TagFormat::Flac) => {
+                let comments = Self::flac_vorbis_comments(reader)?;
+                Ok(comments.and_then(|comments| Self::vorbis_lyrics(&comments)))
+            }
+            Some(TagFormat::Ogg) => {
+                let comments = Self::ogg_vorbis_comments(reader)?;
+                Ok(comments.and_then(|comments| Self::vorbis_lyrics(&comments)))
+            }
+            Some(TagFormat::Mp4) => Self::read_mp4(reader),
+            None => Ok(None),
+        }
+    }
+
+    fn detect_format(magic: &[u8]) -> Option<TagFormat> {
+        if magic.starts_with(b"ID3") {
+            Some(TagFormat::Id3)
+        } else if magic.starts_with(b"fLaC") {
+            Some(TagFormat::Flac)
+        } else if magic.starts_with(b"OggS") {
+            Some(TagFormat::Ogg)
+        } else if magic.get(4..8) == Some(&b"ftyp"[..]) {
+            Some(TagFormat::Mp4)
+        } else {
+            None
+        }
+    }
+
+    /// `SYLT` frames in milliseconds are preferred over `USLT` frames
+    fn read_id3(reader: impl Read + Seek) -> Result<Option<Lyrics>, TagError> {
+        let tag = match id3::Tag::read_from2(reader) {
+            Ok(tag) => tag,
+            Err(id3::Error { kind: id3::ErrorKind::NoTag, .. }) => return Ok(None),
+            // Frames before an invalid one are still usable
+            Err(id3::Error { partial_tag: Some(tag), description, .. }) => {
+                println!("embedded: Using a partial ID3 tag: {}", description);
+                tag
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let synced = tag
+            .synchronised_lyrics()
+            .filter(|sylt| sylt.timestamp_format == TimestampFormat::Ms)
+            .find_map(|sylt| Self::sylt_to_synced(&sylt.content));
+
+        if let Some(synced) = synced {
+            return Ok(Some(Lyrics::Synced(synced)));
+        }
+
+        let plain = tag.lyrics().find_map(|uslt| Lyrics::from_text(&uslt.text));
+        Ok(plain)
+    }
+
+    /// Every `SYLT` entry is a line, some taggers start them with a line break
+    fn sylt_to_synced(content: &[(u32, String)]) -> Option<SyncedLyrics> {
+        let mut lines = content
+            .iter()
+            .map(|(start, text)| SyncedLine {
+                start: Duration::from_millis(*start as u64),
+                text: text.trim_matches(|c| c == '\r' || c == '\n').to_string(),
+                words: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        if lines.is_empty() {
+            return None;
+        }
+        lines.sort_by_key(|line| line.start);
+
+        Some(SyncedLyrics {
+            lines,
+            ..SyncedLyrics::default()
+        })
+    }
+
+    /// Body of the `VORBIS_COMMENT` metadata block
+    fn flac_vorbis_comments(mut reader: impl Read + Seek) -> Result<Option<Vec<u8>>, TagError> {
+        reader.seek(SeekFrom::Start(4))?;
+
+        loop {
+            let mut header = [0; 4];
+            reader.read_exact(&mut header)?;
+
+            let is_last = header[0] & 0x80 != 0;
+            let block_type = header[0] & 0x7f;
+            let length = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+
+            if block_type == VORBIS_COMMENT_BLOCK {
+                let mut block = vec![0; length as usize];
+                reader.read_exact(&mut block)?;
+                return Ok(Some(block));
+            }
+            if is_last {
+                return Ok(None);
+            }
+
+            reader.seek(SeekFrom::Current(length as i64))?;
+        }
+    }
+
+    /// Comments of the first logical stream, they are in its second packet
+    fn ogg_vorbis_comments(mut reader: impl Read) -> Result<Option<Vec<u8>>, TagError> {
+        let mut serial = None;
+        let mut packet_index = 0;
+        let mut packet = Vec::new();
+
+        loop {
+            let mut header = [0; 27];
+            match reader.read_exact(&mut header) {
+                Ok(()) => (),
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(error) => return Err(error.into()),
+            }
+            if &header[0..4] != b"OggS" {
+                return Err(TagError::Malformed("Ogg"));
+            }
+
+            let page_serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+            let mut lacing_values = vec![0; header[26] as usize];
+            reader.read_exact(&mut lacing_values)?;
+            let mut body = vec![0; lacing_values.iter().map(|&value| value as usize).sum()];
+            reader.read_exact(&mut body)?;
+
+            // Pages of other streams are interleaved in multiplexed files
+            if *serial.get_or_insert(page_serial) != page_serial {
+                continue;
+            }
+
+            let mut offset = 0;
+            for &value in &lacing_values {
+                let segment = &body[offset..offset + value as usize];
+                offset += value as usize;
+
+                if packet_index == 1 {
+                    packet.extend_from_slice(segment);
+                }
+                // A segment shorter than 255 bytes ends the packet
+                if value < 255 {
+                    if packet_index == 1 {
+                        return Ok(Self::strip_comment_packet_header(&packet).map(|comments| comments.to_vec()));
+                    }
+                    packet_index += 1;
+                }
+            }
+        }
+    }
+
+    /// Vorbis and Opus put a codec specific header before the comments
+    fn strip_comment_packet_header(packet: &[u8]) -> Option<&[u8]> {
+        if packet.starts_with(b"\x03vorbis") {
+            Some(&packet[7..])
+        } else if packet.starts_with(b"OpusTags") {
+            Some(&packet[8..])
+        } else {
+            None
+        }
+    }
+
+    /// The first lyrics field of a Vorbis comment
+    fn vorbis_lyrics(comments: &[u8]) -> Option<Lyrics> {
+        let mut bytes = comments;
+        let vendor_length = Self::read_u32_le(&mut bytes)?;
+        bytes = bytes.get(vendor_length as usize..)?;
+        let count = Self::read_u32_le(&mut bytes)?;
+
+        for _ in 0..count {
+            let length = Self::read_u32_le(&mut bytes)? as usize;
+            let comment = String::from_utf8_lossy(bytes.get(..length)?);
+            bytes = &bytes[length..];
+
+            if let Some((field, value)) = comment.split_once('=') {
+                let is_lyrics = VORBIS_LYRICS_FIELDS
+                    .iter()
+                    .any(|lyrics_field| field.eq_ignore_ascii_case(lyrics_field));
+
+                if is_lyrics {
+                    if let Some(lyrics) = Lyrics::from_text(value) {
+                        return Some(lyrics);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn read_u32_le(bytes: &mut &[u8]) -> Option<u32> {
+        let value = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?);
+        *bytes = &bytes[4..];
+        Some(value)
+    }
+
+    /// The `moov` atom is read into memory, the media data is skipped
+    fn read_mp4(mut reader: impl Read + Seek) -> Result<Option<Lyrics>, TagError> {
+        loop {
+            let mut header = [0; 8];
+            match reader.read_exact(&mut header) {
+                Ok(()) => (),
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(error) => return Err(error.into()),
+            }
+
+            let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+            let mut header_size = 8;
+            if size == 1 {
+                let mut large_size = [0; 8];
+                reader.read_exact(&mut large_size)?;
+                size = u64::from_be_bytes(large_size);
+                header_size = 16;
+            }
+            // Size 0 is the last atom, up to the end of the file
+            if size == 0 && &header[4..8] != b"moov" {
+                return Ok(None);
+            }
+            if size != 0 && size < header_size {
+                return Err(TagError::Malformed("MP4"));
+            }
+
+            if &header[4..8] == b"moov" {
+                let mut moov = Vec::new();
+                if size == 0 {
+                    reader.read_to_end(&mut moov)?;
+                } else {
+                    reader.take(size - header_size).read_to_end(&mut moov)?;
+                }
+
+                return Ok(Self::find_mp4_atom(&moov, &MP4_LYRICS_PATH)
+                    // Type indicator and locale come before the text
+                    .and_then(|data| data.get(8..))
+                    .and_then(|text| Lyrics::from_text(&String::from_utf8_lossy(text))));
+            }
+
+            reader.seek(SeekFrom::Current((size - header_size) as i64))?;
+        }
+    }
+
+    /// Body of the atom at `path` inside `atoms`
+    fn find_mp4_atom<'a>(mut atoms: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+        let (name, rest) = path.split_first()?;
+
+        while atoms.len() >= 8 {
+            let size = u32::from_be_bytes(atoms[0..4].try_into().ok()?) as usize;
+            if size < 8 || size > atoms.len() {
+                return None;
+            }
+
+            if &atoms[4..8] == *name {
+                let mut body = &atoms[8..size];
+                // `meta` is a full atom with a version and flags, except in some QuickTime files
+                if *name == b"meta" && body.get(4..8) != Some(&b"hdlr"[..]) {
+                    body = body.get(4..)?;
+                }
+
+                return match rest.is_empty() {
+                    true => Some(body),
+                    false => Self::find_mp4_atom(body, rest),
+                };
+            }
+
+            atoms = &atoms[size..];
+        }
+
+        None
+    }
+}
+
+impl LyricsFetcher for EmbeddedTags {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    /// Retagging the file changes the lyrics
+    fn watched_files(&self, metadata: &TrackMetadata) -> Vec<PathBuf> {
+        metadata.local_path().into_iter().collect()
+    }
+
+    fn get_lyrics<'a>(&'a self, metadata: &'a TrackMetadata) -> BoxFuture<'a, LyricsResult> {
+        Box::pin(async move { Self::read_lyrics(metadata) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use crate::{lyrics::{Lyrics, LyricsError}, player::metadata::TrackMetadata};
+
+    use super::EmbeddedTags;
+
+    fn fixture(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "tags", name].iter().collect()
+    }
+
+    fn read_fixture(name: &str) -> Option<Lyrics> {
+        EmbeddedTags::read_file(&fixture(name)).unwrap()
+    }
+
+    fn assert_synced(lyrics: Option<Lyrics>) {
+        match lyrics {
+            Some(Lyrics::Synced(synced)) => {
+                assert_eq!(synced.lines.len(), 2);
+                assert_eq!(synced.lines[0].start, Duration::from_millis(112300));
+                assert_eq!(synced.lines[1].text, "One word");
+            }
+            other => panic!("Expected synced lyrics, got {:?}", other),
+        }
+    }
+
+    fn assert_plain(lyrics: Option<Lyrics>) {
+        match lyrics {
+            Some(Lyrics::Plain(plain)) => assert_eq!(plain, "We are born of one breath\nOne word"),
+            other => panic!("Expected plain lyrics, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn id3_lyrics() {
+        // Has both SYLT and USLT
+        assert_synced(read_fixture("sylt.mp3"));
+        assert_plain(read_fixture("uslt.mp3"));
+    }
+
+    #[test]
+    fn vorbis_comment_lyrics() {
+        assert_plain(read_fixture("lyrics.flac"));
+        // Lowercase `lyrics` field with LRC text
+        assert_synced(read_fixture("lyrics.ogg"));
+        assert_plain(read_fixture("lyrics.opus"));
+    }
+
+    #[test]
+    fn mp4_lyrics() {
+        assert_plain(read_fixture("lyrics.m4a"));
+    }
+
+    #[test]
+    fn files_without_lyrics() {
+        assert!(read_fixture("no_lyrics.flac").is_none());
+        assert!(read_fixture("no_tag.mp3").is_none());
+
+        let metadata = TrackMetadata {
+            url: Some(format!("file://{}", fixture("no_lyrics.flac").display())),
+            ..TrackMetadata::default()
+        };
+        assert!(matches!(EmbeddedTags::read_lyrics(&metadata), Err(LyricsError::SongNotFound { .. })));
+
+        let metadata = TrackMetadata {
+            url: Some(format!("file://{}", fixture("sylt.mp3").display())),
+            ..TrackMetadata::default()
+        };
+        assert!(EmbeddedTags::read_lyrics(&metadata).unwrap().lyrics.is_synced());
+    }
+}
@@ -0,0 +1,228 @@
+use std::{path::{Path, PathBuf}, process::Stdio, time::Duration};
+
+use futures::future::BoxFuture;
+use serde_derive::{Deserialize, Serialize};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::player::metadata::TrackMetadata;
+
+use super::{Lyrics, LyricsError, LyricsFetcher, LyricsResponse, LyricsResult};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Only the end of long error output is reported
+const MAX_STDERR_LENGTH: usize = 500;
+
+/// A user's executable asked for lyrics.
+/// It gets the track as JSON on stdin and writes a JSON response to stdout,
+/// or `null` if it doesn't have the song.
+pub struct ExternalCommand {
+    name: String,
+    path: PathBuf,
+    timeout: Duration,
+}
+
+/// Written to the stdin of the command
+#[derive(Serialize, Debug)]
+struct CommandRequest<'a> {
+    title: &'a str,
+    artists: &'a [String],
+    album: Option<&'a str>,
+    album_artists: &'a [String],
+    length_ms: Option<u64>,
+    url: Option<&'a str>,
+}
+
+/// Read from the stdout of the command, named like the fields of `LyricsResponse`
+#[derive(Deserialize, Debug)]
+struct CommandResponse {
+    artist: Option<String>,
+    track: Option<String>,
+    /// Plain text or LRC
+    lyrics: String,
+    source_url: Option<String>,
+    #[serde(default = "CommandResponse::default_confidence")]
+    confidence: f32,
+}
+
+impl ExternalCommand {
+    /// The provider is named after the file name of the executable
+    pub fn new(path: &Path, timeout: Duration) -> Self {
+        ExternalCommand {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            path: path.to_path_buf(),
+            timeout,
+        }
+    }
+
+    async fn fetch_lyrics(&self, metadata: &TrackMetadata) -> LyricsResult {
+        let request = serde_json::to_vec(&CommandRequest {
+            title: &metadata.title,
+            artists: &metadata.artists,
+            album: metadata.album.as_deref(),
+            album_artists: &metadata.album_artists,
+            length_ms: metadata.length.map(|length| length.as_millis() as u64),
+            url: metadata.url.as_deref(),
+        })
+        .map_err(|error| self.failure(error))?;
+
+        let stdout = match tokio::time::timeout(self.timeout, self.run(&request)).await {
+            Ok(stdout) => stdout?,
+            Err(_) => return Err(self.failure(format!("Timed out after {:?}", self.timeout))),
+        };
+
+        let response = match serde_json::from_slice::<Option<CommandResponse>>(&stdout) {
+            // No output is the same as `null`
+            _ if stdout.iter().all(|byte| byte.is_ascii_whitespace()) => None,
+            Ok(response) => response,
+            Err(error) => return Err(self.failure(format!("Invalid response: {}", error))),
+        };
+
+        response
+            .and_then(|response| response.into_lyrics_response(metadata))
+            .ok_or_else(|| LyricsError::SongNotFound {
+                song_name: metadata.title.clone(),
+                artist: metadata.primary_artist().unwrap_or_default().to_string(),
+            })
+    }
+
+    /// Stdout of the command, it is killed if this is dropped before it exits
+    async fn run(&self, request: &[u8]) -> LyricsResult<Vec<u8>> {
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|error| self.failure(error))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // Commands that don't read the track may exit before it is written
+            if let Err(error) = stdin.write_all(request).await {
+                println!("external: {} didn't read the track: {}", self.name, error);
+            }
+        }
+
+        let output = child.wait_with_output().await.map_err(|error| self.failure(error))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = stderr.trim();
+            let start = stderr
+                .char_indices()
+                .rev()
+                .nth(MAX_STDERR_LENGTH)
+                .map(|(index, _)| index)
+                .unwrap_or(0);
+
+            return Err(self.failure(format!("{}: {}", output.status, &stderr[start..])));
+        }
+
+        Ok(output.stdout)
+    }
+
+    fn failure(&self, reason: impl ToString) -> LyricsError {
+        LyricsError::ProviderFailed {
+            provider: self.name.clone(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl CommandResponse {
+    fn default_confidence() -> f32 {
+        1.0
+    }
+
+    /// `None` if there are no lyrics
+    fn into_lyrics_response(self, metadata: &TrackMetadata) -> Option<LyricsResponse> {
+        Some(LyricsResponse {
+            artist: self
+                .artist
+                .unwrap_or_else(|| metadata.primary_artist().unwrap_or_default().to_string()),
+            track: self.track.unwrap_or_else(|| metadata.title.clone()),
+            lyrics: Lyrics::from_text(&self.lyrics)?,
+            cover_art: Vec::new(),
+            provider: String::new(),
+            source_url: self.source_url,
+            confidence: self.confidence.clamp(0.0, 1.0),
+        })
+    }
+}
+
+impl LyricsFetcher for ExternalCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_lyrics<'a>(&'a self, metadata: &'a TrackMetadata) -> BoxFuture<'a, LyricsResult> {
+        Box::pin(self.fetch_lyrics(metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use tokio::runtime::Runtime;
+
+    use crate::{
+        lyrics::{Lyrics, LyricsError, LyricsFetcher, LyricsResult},
+        player::metadata::TrackMetadata,
+    };
+
+    use super::ExternalCommand;
+
+    fn run(script: &str, title: &str, timeout: Duration) -> LyricsResult {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "commands", script].iter().collect();
+        let command = ExternalCommand::new(&path, timeout);
+        assert_eq!(command.name(), script);
+
+        let metadata = TrackMetadata {
+            title: title.to_string(),
+            artists: vec!["TOOL".to_string()],
+            length: Some(Duration::from_secs(713)),
+            ..TrackMetadata::default()
+        };
+
+        Runtime::new().unwrap().block_on(command.get_lyrics(&metadata))
+    }
+
+    #[test]
+    fn responses_are_read() {
+        let synced = run("lyrics.sh", "Pneuma", Duration::from_secs(10)).unwrap();
+        assert!(matches!(synced.lyrics, Lyrics::Synced(_)));
+        assert_eq!(synced.artist, "TOOL");
+        assert_eq!(synced.source_url.as_deref(), Some("https://lyrics.example.com/pneuma"));
+        assert_eq!(synced.confidence, 0.9);
+
+        let plain = run("lyrics.sh", "Invincible", Duration::from_secs(10)).unwrap();
+        assert_eq!(plain.lyrics.to_plain(), "Long ago\nA warrior");
+        assert_eq!(plain.confidence, 1.0);
+
+        let not_found = run("lyrics.sh", "Descending", Duration::from_secs(10));
+        assert!(matches!(not_found, Err(LyricsError::SongNotFound { .. })));
+    }
+
+    #[test]
+    fn failures_are_errors() {
+        match run("fail.sh", "Pneuma", Duration::from_secs(10)) {
+            Err(LyricsError::ProviderFailed { provider, reason }) => {
+                assert_eq!(provider, "fail.sh");
+                assert!(reason.ends_with("Lyrics server is down"), "{}", reason);
+            }
+            other => panic!("Expected a failure, got {:?}", other),
+        }
+
+        let invalid = run("invalid.sh", "Pneuma", Duration::from_secs(10));
+        assert!(matches!(invalid, Err(LyricsError::ProviderFailed { .. })));
+
+        let timed_out = run("slow.sh", "Pneuma", Duration::from_millis(200));
+        assert!(matches!(timed_out, Err(LyricsError::ProviderFailed { .. })));
+
+        let missing = run("missing.sh", "Pneuma", Duration::from_secs(10));
+        assert!(matches!(missing, Err(LyricsError::ProviderFailed { .. })));
+    }
+}
@@ -0,0 +1,74 @@
+use std::{fs, path::{Path, PathBuf}, time::SystemTime};
+
+/// Notices files being created, changed or removed by polling their modification times.
+/// The lyrics fetcher thread has no main loop for `gio::FileMonitor`.
+#[derive(Default)]
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl FileWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        FileWatcher {
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = Self::modified(&path);
+                    (path, modified)
+                })
+                .collect(),
+        }
+    }
+
+    /// True if any of the files changed since the last poll
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+
+        for (path, modified) in &mut self.files {
+            let new_modified = Self::modified(path);
+            if new_modified != *modified {
+                println!("file_watcher: {:?} changed", path);
+                *modified = new_modified;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// `None` if the file doesn't exist
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::{Duration, SystemTime}};
+
+    use super::FileWatcher;
+
+    #[test]
+    fn changes_are_noticed() {
+        let path = std::env::temp_dir().join(format!("sync-lyrics-watcher-{}.lrc", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut watcher = FileWatcher::new(vec![path.clone()]);
+        assert!(!watcher.poll());
+
+        fs::write(&path, "[00:01.00]Immunity").unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert!(watcher.poll());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.poll());
+    }
+}
@@ -1,16 +1,28 @@
 use std::{usize};
 
-use futures::try_join;
+use futures::{future::BoxFuture, try_join};
 use reqwest::{Client, ClientBuilder};
-use scraper::{Html, Selector};
+use scraper::{node::Element, ElementRef, Html, Node, Selector};
 use serde_derive::{Deserialize, Serialize};
 
-use crate::configs::NETWORK_TIMEOUT_DURATION;
+use crate::{configs::NETWORK_TIMEOUT_DURATION, player::metadata::TrackMetadata};
 
-use super::{LyricsError, LyricsResponse, LyricsResult};
+use super::{matching::{self, SongDescription}, normalize::{self, SongQuery}, Lyrics, LyricsError, LyricsFetcher, LyricsResponse, LyricsResult};
 
-static BASE_ENDPOINT: &'static str = "https://api.genius.com/";
+pub const DEFAULT_BASE_URL: &'static str = "https://api.genius.com";
 static ACCESS_TOKEN: &'static str = env!("ACCESS_TOKEN");
+static PROVIDER_NAME: &'static str = "Genius";
+/// Hits scoring less are not taken as the song
+const MIN_MATCH_SCORE: f32 = 0.6;
+/// Lyrics containers of the page layouts Genius has used, from the oldest to the newest
+static LYRICS_SELECTOR: &'static str = "div.lyrics, div[class^=\"Lyrics__Container\"], div[data-lyrics-container=\"true\"]";
+/// Headers, footers, ads and song recommendations found in or around lyrics containers
+const EXCLUDED_CLASSES: [&'static str; 6] = [
+    "LyricsHeader__", "LyricsFooter__", "Lyrics__Footer", "RightSidebar__", "InreadContainer__", "SidebarAd__",
+];
+const EXCLUDED_ELEMENTS: [&'static str; 5] = ["script", "style", "noscript", "iframe", "button"];
+/// Title of the song recommendations that are inserted between lines
+static RECOMMENDATIONS_TITLE: &'static str = "You might also like";
 
 #[derive(Serialize, Deserialize, Debug)]
 struct SongResponseWrapper {
@@ -54,28 +66,39 @@ struct SearchResponseEntry {
 #[derive(Serialize, Deserialize, Debug)]
 struct SearchResponseResult {
     id: usize,
+    #[serde(default)]
+    title: String,
+    primary_artist: Option<SearchResponseArtist>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+struct SearchResponseArtist {
+    name: String,
+}
+
+/// Client of the Genius api, or a server with the same api
 pub struct Genius {
     client: Client,
+    base_url: String,
 }
 
 impl Genius {
-    pub fn new() -> Self {
+    pub fn new(base_url: &str) -> Self {
         Genius {
             client: ClientBuilder::new()
                 .timeout(NETWORK_TIMEOUT_DURATION)
                 .build()
                 .unwrap(),
+            base_url: base_url.trim_end_matches('/').to_string(),
         }
     }
 
-    pub async fn get_lyrics(&mut self, song_title: &str, artist: &str) -> LyricsResult {
-        
-        let song_info = {
-            let song_id = self.request_song_id(song_title, artist).await?;
-            self.request_song_info(song_id).await?
-        };
+    async fn fetch_lyrics(&self, metadata: &TrackMetadata) -> LyricsResult {
+        let song_title = metadata.title.as_str();
+        let artist = metadata.primary_artist().unwrap_or_default();
+
+        let (song_id, score) = self.request_song_id(metadata).await?;
+        let song_info = self.request_song_info(song_id).await?;
         
         let lyrics_future = {
             let song_url = &song_info.url;
@@ -91,9 +114,14 @@ impl Genius {
     
                 println!("Lyrics fetched");
 
-                let lyrics = Genius::extract_lyrics(html);  
-    
-                Ok(lyrics)
+                // Instrumentals and unreleased songs have pages without lyrics
+                Genius::extract_lyrics(&html).ok_or_else(|| {
+                    println!("genius: No lyrics found on {}", song_url);
+                    LyricsError::SongNotFound {
+                        song_name: song_title.to_string(),
+                        artist: artist.to_string(),
+                    }
+                })
             }
         };
 
@@ -111,13 +139,16 @@ impl Genius {
         Ok(LyricsResponse {
             track: song_title.into(),
             artist: artist.into(),
-            lyrics: lyrics,
+            lyrics: Lyrics::from_sectioned_text(&lyrics),
             cover_art,
+            provider: self.name().into(),
+            source_url: Some(song_info.url),
+            confidence: score,
         })
     }
 
-    async fn request_song_info(&mut self, song_id: usize) -> LyricsResult<SongResponseData> {
-        let url = BASE_ENDPOINT.to_string() + "songs/" + &song_id.to_string();
+    async fn request_song_info(&self, song_id: usize) -> LyricsResult<SongResponseData> {
+        let url = format!("{}/songs/{}", self.base_url, song_id);
 
         let resp: SongResponseWrapper = self
             .client
@@ -131,9 +162,68 @@ impl Genius {
         Ok(resp.response.song)
     }
 
-    async fn request_song_id(&mut self, song_title: &str, artist: &str) -> LyricsResult<usize> {
-        let url = BASE_ENDPOINT.to_string() + "search";
-        let query: [(&str, &str); 1] = [("q", &(song_title.to_owned() + " " + artist))];
+    /// Id of the search hit that is most likely the song and its score.
+    /// Looser searches are tried until one of them finds a hit that is confidently the song.
+    async fn request_song_id(&self, metadata: &TrackMetadata) -> LyricsResult<(usize, f32)> {
+        let credited_artists = normalize::credited_artists(metadata);
+        // Any of the artists may be the primary one on Genius
+        let wanted = SongDescription {
+            artists: credited_artists.iter().map(|artist| artist.as_str()).collect(),
+            ..SongDescription::from(metadata)
+        };
+
+        for (stage, query) in Self::search_stages(metadata) {
+            let search_text = query.search_text();
+            let hits = self.search(&search_text).await?;
+
+            match Self::best_hit(&hits, &wanted) {
+                Some((hit, score)) if score >= MIN_MATCH_SCORE => {
+                    println!("genius: Searching {} {:?} found {} with score {:.2}", stage, search_text, hit.result.title, score);
+                    return Ok((hit.result.id, score));
+                }
+                Some((hit, score)) => {
+                    println!("genius: Searching {} {:?} found only {} with score {:.2}", stage, search_text, hit.result.title, score);
+                }
+                None => println!("genius: Searching {} {:?} found no songs", stage, search_text),
+            }
+        }
+
+        Err(LyricsError::SongNotFound {
+            song_name: metadata.title.clone(),
+            artist: metadata.primary_artist().unwrap_or_default().to_string(),
+        })
+    }
+
+    /// Searches with the shared query variants, and with each credited artist before the title alone,
+    /// since any of them may be the primary artist on Genius
+    fn search_stages(metadata: &TrackMetadata) -> Vec<(&'static str, SongQuery)> {
+        let title = normalize::clean_title(&metadata.title).title;
+        let (with_artist, title_only): (Vec<_>, Vec<_>) = normalize::query_variants(metadata)
+            .into_iter()
+            .partition(|query| query.artist.is_some());
+
+        let credited = normalize::credited_artists(metadata).into_iter().map(|artist| SongQuery {
+            title: title.clone(),
+            artist: Some(artist),
+        });
+        let stages = with_artist
+            .into_iter()
+            .map(|query| ("title and artist", query))
+            .chain(credited.map(|query| ("title and credited artist", query)))
+            .chain(title_only.into_iter().map(|query| ("title only", query)));
+
+        let mut unique: Vec<(&'static str, SongQuery)> = Vec::new();
+        for (stage, query) in stages {
+            if !unique.iter().any(|(_, known)| known.search_text() == query.search_text()) {
+                unique.push((stage, query));
+            }
+        }
+        unique
+    }
+
+    async fn search(&self, search_text: &str) -> LyricsResult<Vec<SearchResponseEntry>> {
+        let url = format!("{}/search", self.base_url);
+        let query: [(&str, &str); 1] = [("q", search_text)];
 
         let resp: SearchResponse = self
             .client
@@ -145,16 +235,36 @@ impl Genius {
             .json()
             .await?;
 
-        match resp.response.hits.get(0) {
-            Some(hit) => Ok(hit.result.id),
-            None => Err(LyricsError::SongNotFound { 
-                song_name: song_title.to_string(), 
-                artist: artist.to_string() 
-            }),
+        Ok(resp.response.hits)
+    }
+
+    /// Song hit with the highest score, the earlier one on ties.
+    /// Genius doesn't tell the lengths of songs, only titles and artists are compared.
+    fn best_hit<'a>(hits: &'a [SearchResponseEntry], wanted: &SongDescription) -> Option<(&'a SearchResponseEntry, f32)> {
+        let mut best: Option<(&SearchResponseEntry, f32)> = None;
+
+        for hit in hits.iter().filter(|hit| hit.type_ == "song") {
+            let found = SongDescription {
+                title: &hit.result.title,
+                artists: hit.result.primary_artist.iter().map(|artist| artist.name.as_str()).collect(),
+                length: None,
+            };
+            let score = matching::match_score(wanted, &found);
+            println!("genius: {} by {:?} scored {:.2}", found.title, found.artists, score);
+
+            let is_better = match best {
+                Some((_, best_score)) => score > best_score,
+                None => true,
+            };
+            if is_better {
+                best = Some((hit, score));
+            }
         }
+
+        best
     }
 
-    async fn get_cover_art(&mut self, album: &SongResponseAlbum) -> LyricsResult<Vec<u8>> {
+    async fn get_cover_art(&self, album: &SongResponseAlbum) -> LyricsResult<Vec<u8>> {
         // TODO convert this to 300x300 url
         let url = &album.cover_art_url;
 
@@ -169,106 +279,300 @@ impl Genius {
         Ok(resp.into_iter().collect())
     }
 
-    fn extract_lyrics(html: String) -> String {
-        let doc = Html::parse_document(&html);
-        let selector1 = Selector::parse("div.lyrics").unwrap();
-        let selector2 = Selector::parse("div[class^=\"Lyrics__Container\"]").unwrap();
-
-        let lyrics_divs = doc.select(&selector1).chain(doc.select(&selector2));
-
-        lyrics_divs
-            .map(|d| {
-                d.text()
-                    .map(|s| if s.contains("\n") { s.trim() } else { s })
-                    .filter(|&s| s.chars().any(|c| !c.is_whitespace()))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            })
-            .collect::<Vec<_>>()
-            .join("")
-            .replace("[", "\n[")
-            .trim()
-            .to_string()
+    /// Lyrics on a song page, `None` if there are none.
+    /// Lines are kept as on the page with at most one blank line between stanzas.
+    fn extract_lyrics(html: &str) -> Option<String> {
+        let doc = Html::parse_document(html);
+        let selector = Selector::parse(LYRICS_SELECTOR).unwrap();
+
+        let mut text = String::new();
+        for container in doc.select(&selector) {
+            // Containers of some layouts match more than one selector
+            let is_nested = container
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|ancestor| selector.matches(&ancestor));
+            if !is_nested {
+                Self::append_text(container, &mut text);
+                text.push('\n');
+            }
+        }
+
+        let mut lines: Vec<&str> = Vec::new();
+        for line in text.lines().map(str::trim) {
+            let follows_blank = matches!(lines.last(), None | Some(&""));
+
+            if line.eq_ignore_ascii_case(RECOMMENDATIONS_TITLE) || (line.is_empty() && follows_blank) {
+                continue;
+            }
+            // Containers end wherever an ad is, not between stanzas
+            if Self::is_section_header(line) && !follows_blank {
+                lines.push("");
+            }
+            lines.push(line);
+        }
+
+        // Share buttons used to be read as `Embed` after the last line, with their count before it
+        if let Some(last) = lines.last_mut() {
+            if let Some(rest) = last.strip_suffix("Embed") {
+                let without_count = rest.trim_end_matches(|c: char| c.is_ascii_digit());
+                // Without the count it is a lyric
+                if without_count.len() < rest.len() {
+                    *last = without_count.trim_end();
+                }
+            }
+        }
+
+        let lyrics = lines.join("\n").trim().to_string();
+        if lyrics.is_empty() {
+            None
+        } else {
+            Some(lyrics)
+        }
+    }
+
+    /// Text of `element` as a browser shows it, `<br>` and blocks are line breaks
+    fn append_text(element: ElementRef, text: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(child_text) => {
+                    let child_text: &str = child_text;
+                    if child_text.starts_with(char::is_whitespace) {
+                        text.push(' ');
+                    }
+                    text.push_str(&child_text.split_whitespace().collect::<Vec<_>>().join(" "));
+                    if child_text.ends_with(char::is_whitespace) && !child_text.trim().is_empty() {
+                        text.push(' ');
+                    }
+                }
+                Node::Element(child_element) if Self::is_excluded(child_element) => (),
+                Node::Element(child_element) => {
+                    let child = ElementRef::wrap(child).unwrap();
+
+                    match child_element.name() {
+                        "br" => text.push('\n'),
+                        "p" | "div" => {
+                            text.push('\n');
+                            Self::append_text(child, text);
+                            text.push('\n');
+                        }
+                        _ => Self::append_text(child, text),
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Parts of lyrics containers that are not lyrics
+    fn is_excluded(element: &Element) -> bool {
+        element.attr("data-exclude-from-selection") == Some("true")
+            || EXCLUDED_ELEMENTS.contains(&element.name())
+            || element
+                .classes()
+                .any(|class| EXCLUDED_CLASSES.iter().any(|excluded| class.starts_with(excluded)))
+    }
+
+    fn is_section_header(line: &str) -> bool {
+        line.starts_with('[') && line.ends_with(']')
+    }
+}
+
+impl LyricsFetcher for Genius {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn get_lyrics<'a>(&'a self, metadata: &'a TrackMetadata) -> BoxFuture<'a, LyricsResult> {
+        Box::pin(self.fetch_lyrics(metadata))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Genius;
+    use std::{fs, path::PathBuf};
+
+    use crate::{
+        lyrics::{
+            matching::SongDescription,
+            stub_server::{StubResponse, StubServer},
+            Lyrics, LyricsError, LyricsFetcher,
+        },
+        player::metadata::TrackMetadata,
+    };
+
+    use super::{Genius, SearchResponse};
+
+    fn fixtures() -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "genius"].iter().collect()
+    }
+
+    fn tool_song(title: &str) -> TrackMetadata {
+        TrackMetadata {
+            title: title.to_string(),
+            artists: vec!["TOOL".to_string()],
+            ..TrackMetadata::default()
+        }
+    }
+
+    fn genius_server() -> StubServer {
+        StubServer::start(vec![
+            ("/search", StubResponse::fixture("genius/search.json")),
+            ("/songs/4719212", StubResponse::fixture("genius/song.json")),
+            ("/Tool-fear-inoculum-lyrics", StubResponse::fixture("genius/modern.html")),
+            ("/images/fear-inoculum.png", StubResponse::fixture("genius/cover.png")),
+        ])
+    }
 
     #[tokio::test]
-    async fn find_songpage_works() {
-        let mut genius = Genius::new();
+    async fn lyrics_are_fetched() {
+        let server = genius_server();
+        let genius = Genius::new(server.base_url());
+
+        let response = genius.get_lyrics(&tool_song("Fear Inoculum")).await.unwrap();
+
+        let expected = fs::read_to_string(fixtures().join("modern.txt")).unwrap();
+        assert!(matches!(response.lyrics, Lyrics::Sectioned(_)));
+        assert_eq!(response.lyrics.to_plain(), expected.trim());
+        assert_eq!(response.cover_art, fs::read(fixtures().join("cover.png")).unwrap());
+        assert_eq!(response.source_url, Some(format!("{}/Tool-fear-inoculum-lyrics", server.base_url())));
+        assert_eq!(response.confidence, 1.0);
+
+        let mut requests = server.requests();
+        // The page and the cover art are fetched at once
+        requests[2..].sort();
+        assert_eq!(requests, vec![
+            "/search?q=Fear+Inoculum+TOOL",
+            "/songs/4719212",
+            "/Tool-fear-inoculum-lyrics",
+            "/images/fear-inoculum.png",
+        ]);
+    }
 
-        let song_id = genius.request_song_id("HUMBLE", "Kendrick Lamar").await.unwrap();
-        let song_info = genius.request_song_info(song_id).await.unwrap();
-        let found = song_info.url;
-        assert_eq!(&found, "https://genius.com/Kendrick-lamar-humble-lyrics");
+    #[tokio::test]
+    async fn missing_songs_are_searched_loosely() {
+        let server = genius_server();
+        let genius = Genius::new(server.base_url());
+
+        let not_found = genius.get_lyrics(&tool_song("Pneuma")).await;
+        assert!(matches!(not_found, Err(LyricsError::SongNotFound { .. })));
+        assert_eq!(server.requests(), vec!["/search?q=Pneuma+TOOL", "/search?q=Pneuma"]);
+
+        // The song page has no lyrics
+        let server = StubServer::start(vec![
+            ("/search", StubResponse::fixture("genius/search.json")),
+            ("/songs/4719212", StubResponse::fixture("genius/song.json")),
+            ("/Tool-fear-inoculum-lyrics", StubResponse::fixture("genius/no_lyrics.html")),
+            ("/images/fear-inoculum.png", StubResponse::fixture("genius/cover.png")),
+        ]);
+        let instrumental = Genius::new(server.base_url()).get_lyrics(&tool_song("Fear Inoculum")).await;
+        assert!(matches!(instrumental, Err(LyricsError::SongNotFound { .. })), "{:?}", instrumental);
+
+        // The song page is gone
+        let server = StubServer::start(vec![
+            ("/search", StubResponse::fixture("genius/search.json")),
+            ("/songs/4719212", StubResponse::fixture("genius/song.json")),
+            ("/images/fear-inoculum.png", StubResponse::fixture("genius/cover.png")),
+        ]);
+        let gone = Genius::new(server.base_url()).get_lyrics(&tool_song("Fear Inoculum")).await;
+        assert!(matches!(gone, Err(LyricsError::SongNotFound { .. })), "{:?}", gone);
+    }
+
+    #[test]
+    fn best_hit_is_the_song() {
+        let search: SearchResponse = serde_json::from_str(r#"{"response": {"hits": [
+            {"type": "song", "result": {"id": 1, "title": "HUMBLE. (Traducción al Español)", "primary_artist": {"name": "Genius Traducciones al Español"}}},
+            {"type": "album", "result": {"id": 2, "title": "HUMBLE.", "primary_artist": {"name": "Kendrick Lamar"}}},
+            {"type": "song", "result": {"id": 3, "title": "HUMBLE.", "primary_artist": {"name": "Boyce Avenue"}}},
+            {"type": "song", "result": {"id": 4, "title": "HUMBLE.", "primary_artist": {"name": "Kendrick Lamar"}}}
+        ]}}"#).unwrap();
+        let hits = &search.response.hits;
+
+        let wanted = SongDescription {
+            title: "HUMBLE.",
+            artists: vec!["Kendrick Lamar"],
+            length: None,
+        };
+        let (hit, score) = Genius::best_hit(hits, &wanted).unwrap();
+        assert_eq!(hit.result.id, 4);
+        assert_eq!(score, 1.0);
+
+        // Only wrong songs
+        let (_, score) = Genius::best_hit(&hits[..3], &wanted).unwrap();
+        assert!(score < super::MIN_MATCH_SCORE);
+
+        assert!(Genius::best_hit(&[], &wanted).is_none());
     }
 
     #[test]
-    fn extract_lyrics_works() {
-        let url = "https://genius.com/Tool-fear-inoculum-lyrics";
-
-        let answer = "[Verse 1]
-Immunity, long overdue
-Contagion, I exhale you
-Naive, I opened up to you
-Venom and mania
-Now, contagion, I exhale you
-[Interlude]
-The deceiver says, he says
-\"You belong to me
-You don't wanna breathe the light of the others
-Fear the light, fear the breath
-Fear the others for eternity\"
-But I hear them now, inhale the clarity
-Hear the venom, the venom in
-What you say inoculated
-Bless this immunity
-Bless this immunity
-Bless this immunity
-[Chorus]
-Exhale, expel
-Recast my tale
-Weave my allegorical elegy
-[Verse 2]
-Enumerate all that I'm to do
-Calculating steps away from you
-My own mitosis
-Growing through division from mania
-[Chorus]
-Exhale, expel
-Recast my tale
-Weave my allegorical elegy
-[Bridge]
-Forfeit all control
-You poison, you spectacle
-Exorcise the spectacle
-Exorcise the malady
-Exorcise the disparate
-Poison for eternity
-Purge me and evacuate
-The venom and the fear that binds me
-[Outro]
-Your veil now, lift away
-I see you runnin'
-Deceiver chased away
-A long time comin'"
-            .split("\n")
-            .map(|s| s.trim())
-            .collect::<Vec<_>>();
-
-        let html = reqwest::blocking::get(url).unwrap().text().unwrap();
-
-        let lyrics = Genius::extract_lyrics(html);
-
-        let lyrics = lyrics
-            .split("\n")
-            .filter(|&s| s.chars().any(|c| !c.is_whitespace()))
-            .map(|s| s.trim())
-            .collect::<Vec<_>>();
-
-        assert_eq!(answer, lyrics);
+    fn searches_get_looser() {
+        let cases = [
+            ("Pneuma", vec!["TOOL"], vec!["Pneuma TOOL", "Pneuma"]),
+            (
+                "Get Lucky (feat. Pharrell Williams) - Radio Edit",
+                vec!["Daft Punk feat. Nile Rodgers"],
+                vec![
+                    "Get Lucky Daft Punk",
+                    "Get Lucky (feat. Pharrell Williams) - Radio Edit Daft Punk feat. Nile Rodgers",
+                    "Get Lucky Nile Rodgers",
+                    "Get Lucky Pharrell Williams",
+                    "Get Lucky",
+                ],
+            ),
+            ("Pneuma", vec![], vec!["Pneuma"]),
+            ("Déjà Vu", vec!["Crosby, Stills, Nash & Young"], vec![
+                "Déjà Vu Crosby, Stills, Nash & Young",
+                "Deja Vu Crosby, Stills, Nash & Young",
+                "Déjà Vu Crosby",
+                "Déjà Vu Stills",
+                "Déjà Vu Nash",
+                "Déjà Vu Young",
+                "Déjà Vu",
+            ]),
+        ];
+
+        for (title, artists, searches) in cases {
+            let metadata = TrackMetadata {
+                title: title.to_string(),
+                artists: artists.iter().map(|artist| artist.to_string()).collect(),
+                ..TrackMetadata::default()
+            };
+            let search_texts = Genius::search_stages(&metadata)
+                .iter()
+                .map(|(_, query)| query.search_text())
+                .collect::<Vec<_>>();
+            assert_eq!(search_texts, searches, "{}", title);
+        }
+    }
+
+    #[test]
+    fn lyrics_are_extracted_from_every_layout() {
+        let fixtures = fixtures();
+
+        for layout in ["legacy", "containers", "modern"] {
+            let html = fs::read_to_string(fixtures.join(format!("{}.html", layout))).unwrap();
+            let expected = fs::read_to_string(fixtures.join(format!("{}.txt", layout))).unwrap();
+
+            assert_eq!(Genius::extract_lyrics(&html).as_deref(), Some(expected.trim()), "{}", layout);
+        }
+
+        let html = fs::read_to_string(fixtures.join("no_lyrics.html")).unwrap();
+        assert_eq!(Genius::extract_lyrics(&html), None);
+    }
+
+    #[test]
+    fn lyrics_are_cleaned_up() {
+        let cases = [
+            // Containers split stanzas at ads
+            (r#"<div class="lyrics">[Verse]<br>One</div><div class="lyrics">[Chorus]<br>Two</div>"#, "[Verse]\nOne\n\n[Chorus]\nTwo"),
+            (r#"<div class="lyrics">One<br><br><br>Two<br>You might also like<br>Three 12Embed</div>"#, "One\n\nTwo\nThree"),
+            (r#"<div class="lyrics">Words you can't Embed</div>"#, "Words you can't Embed"),
+            (r#"<div class="lyrics">Five   <a href="/1"><span>spaces</span></a>
+                here &amp; there</div>"#, "Five spaces here & there"),
+        ];
+
+        for (html, lyrics) in cases {
+            assert_eq!(Genius::extract_lyrics(html).as_deref(), Some(lyrics), "{}", html);
+        }
     }
 }
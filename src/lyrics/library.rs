@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::{sleep, spawn},
+    time::{Duration, SystemTime},
+};
+
+use futures::future::BoxFuture;
+
+use crate::player::metadata::TrackMetadata;
+
+use super::{lrc::SyncedLyrics, normalize, Lyrics, LyricsError, LyricsFetcher, LyricsResponse, LyricsResult};
+
+static PROVIDER_NAME: &'static str = "Lyrics library";
+static LIBRARY_EXTENSION: &'static str = "lrc";
+/// How often the directories are checked for changed files
+const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Normalized artist and title
+type SongKey = (String, String);
+
+/// `.lrc` files in the configured directories, found by artist and title.
+/// The index is kept up to date by a thread that rescans the directories.
+pub struct LyricsLibrary {
+    index: Arc<Mutex<LibraryIndex>>,
+}
+
+#[derive(Default)]
+struct LibraryIndex {
+    dirs: Vec<PathBuf>,
+    /// Every lyrics file with its modification time and song, `None` if the song couldn't be told
+    files: HashMap<PathBuf, (Option<SystemTime>, Option<SongKey>)>,
+    songs: HashMap<SongKey, PathBuf>,
+}
+
+impl LyricsLibrary {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        let mut index = LibraryIndex {
+            dirs,
+            ..LibraryIndex::default()
+        };
+        index.scan();
+
+        let index = Arc::new(Mutex::new(index));
+
+        // Stops when the library is dropped
+        let watched_index = Arc::downgrade(&index);
+        spawn(move || loop {
+            sleep(SCAN_INTERVAL);
+            match watched_index.upgrade() {
+                Some(index) => index.lock().unwrap().scan(),
+                None => break,
+            }
+        });
+
+        LyricsLibrary { index }
+    }
+
+    fn find(&self, metadata: &TrackMetadata) -> Option<PathBuf> {
+        let key = LibraryIndex::key(metadata.primary_artist()?, &metadata.title);
+
+        self.index.lock().unwrap().songs.get(&key).cloned()
+    }
+
+    fn read_lyrics(&self, metadata: &TrackMetadata) -> LyricsResult {
+        let lyrics = self.find(metadata).and_then(|path| match fs::read_to_string(&path) {
+            Ok(text) => Lyrics::from_text(&text).map(|lyrics| (lyrics, path)),
+            Err(error) => {
+                println!("library: Couldn't read {:?}: {}", path, error);
+                None
+            }
+        });
+
+        match lyrics {
+            Some((lyrics, path)) => Ok(LyricsResponse {
+                artist: metadata.primary_artist().unwrap_or_default().to_string(),
+                track: metadata.title.clone(),
+                lyrics,
+                cover_art: Vec::new(),
+                provider: PROVIDER_NAME.to_string(),
+                source_url: Some(format!("file://{}", path.display())),
+                confidence: 1.0,
+            }),
+            None => Err(LyricsError::SongNotFound {
+                song_name: metadata.title.clone(),
+                artist: metadata.primary_artist().unwrap_or_default().to_string(),
+            }),
+        }
+    }
+}
+
+impl LibraryIndex {
+    /// Reads the files that are new or changed since the last scan and forgets the removed ones
+    fn scan(&mut self) {
+        let mut found = HashMap::new();
+        for dir in &self.dirs {
+            if let Err(error) = Self::find_files(dir, &mut found) {
+                println!("library: Couldn't scan {:?}: {}", dir, error);
+            }
+        }
+
+        let removed = self.files.len();
+        self.files.retain(|path, _| found.contains_key(path));
+        let mut changed = removed != self.files.len();
+
+        for (path, modified) in found {
+            let is_indexed = matches!(self.files.get(&path), Some((indexed_modified, _)) if *indexed_modified == modified);
+            if !is_indexed {
+                let key = self.song_of(&path);
+                self.files.insert(path, (modified, key));
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.index_songs();
+        }
+    }
+
+    /// Lyrics files under `dir` with their modification times, symbolic links to directories are not followed
+    fn find_files(dir: &Path, found: &mut HashMap<PathBuf, Option<SystemTime>>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if entry.file_type()?.is_dir() {
+                if let Err(error) = Self::find_files(&path, found) {
+                    println!("library: Couldn't scan {:?}: {}", path, error);
+                }
+            } else if Self::is_lyrics_file(&path) {
+                let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+                found.insert(path, modified);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_lyrics_file(path: &Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.eq_ignore_ascii_case(LIBRARY_EXTENSION))
+            .unwrap_or(false)
+    }
+
+    /// `[ar:]` and `[ti:]` tags are preferred over the file name, which is either `Artist - Title.lrc`
+    /// or `Title.lrc` in a directory named after the artist
+    fn song_of(&self, path: &Path) -> Option<SongKey> {
+        let tags = match fs::read_to_string(path) {
+            Ok(text) => text.parse::<SyncedLyrics>().map(|synced| synced.metadata).unwrap_or_default(),
+            Err(error) => {
+                println!("library: Couldn't read {:?}: {}", path, error);
+                return None;
+            }
+        };
+
+        let stem = path.file_stem()?.to_string_lossy();
+        let (file_artist, file_title) = match stem.split_once(" - ") {
+            // `01 - Title.lrc`
+            Some((number, title)) if number.trim().chars().all(|c| c.is_ascii_digit()) => (None, title.to_string()),
+            Some((artist, title)) => (Some(artist.to_string()), title.to_string()),
+            None => (None, stem.to_string()),
+        };
+
+        let artist = tags
+            .artist
+            .or(file_artist)
+            .or_else(|| self.artist_dir(path))?;
+        let title = tags.title.unwrap_or(file_title);
+
+        Some(Self::key(&artist, &title))
+    }
+
+    /// Name of the top directory of `path` in the library, the songs of an artist are usually kept together
+    fn artist_dir(&self, path: &Path) -> Option<String> {
+        let relative = self.dirs.iter().find_map(|dir| path.strip_prefix(dir).ok())?;
+        let mut components = relative.components();
+
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(artist)), Some(_)) => Some(artist.to_string_lossy().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Files with the same song are picked in path order so that scans agree
+    fn index_songs(&mut self) {
+        let mut paths = self.files.keys().collect::<Vec<_>>();
+        paths.sort();
+
+        let mut songs = HashMap::new();
+        for path in paths {
+            if let (_, Some(key)) = &self.files[path] {
+                songs.entry(key.clone()).or_insert_with(|| path.clone());
+            }
+        }
+
+        let unknown = self.files.values().filter(|(_, key)| key.is_none()).count();
+        println!("library: Indexed {} songs, couldn't tell the song of {} files", songs.len(), unknown);
+
+        self.songs = songs;
+    }
+
+    fn key(artist: &str, title: &str) -> SongKey {
+        (normalize::normalize_artist(artist), normalize::normalize_title(title))
+    }
+}
+
+impl LyricsFetcher for LyricsLibrary {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn watched_files(&self, metadata: &TrackMetadata) -> Vec<PathBuf> {
+        self.find(metadata).into_iter().collect()
+    }
+
+    fn get_lyrics<'a>(&'a self, metadata: &'a TrackMetadata) -> BoxFuture<'a, LyricsResult> {
+        Box::pin(async move { self.read_lyrics(metadata) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, time::{Duration, SystemTime}};
+
+    use super::LibraryIndex;
+
+    fn library(name: &str, files: &[(&str, &str)]) -> (LibraryIndex, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("sync-lyrics-library-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        for (path, text) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        let mut index = LibraryIndex {
+            dirs: vec![dir.clone()],
+            ..LibraryIndex::default()
+        };
+        index.scan();
+
+        (index, dir)
+    }
+
+    fn find(index: &LibraryIndex, artist: &str, title: &str) -> Option<PathBuf> {
+        index.songs.get(&LibraryIndex::key(artist, title)).cloned()
+    }
+
+    #[test]
+    fn songs_are_told_from_tags_and_names() {
+        let (index, dir) = library("names", &[
+            ("TOOL/Fear Inoculum/Pneuma.lrc", "[00:01.00]We are born of one breath"),
+            ("Deftones - Digital Bath.LRC", "[00:01.00]Tonight I feel like more"),
+            ("Muse/01 - Hysteria.lrc", "[00:01.00]It's bugging me"),
+            ("misc/track.lrc", "[ar:Massive Attack]\n[ti:Teardrop]\n[00:01.00]Love, love is a verb"),
+            ("unknown.lrc", "[00:01.00]No artist"),
+            ("notes.txt", "Not lyrics"),
+        ]);
+
+        assert_eq!(find(&index, "tool", "pneuma"), Some(dir.join("TOOL/Fear Inoculum/Pneuma.lrc")));
+        assert_eq!(find(&index, "Deftones", "Digital  Bath"), Some(dir.join("Deftones - Digital Bath.LRC")));
+        assert_eq!(find(&index, "Muse", "Hysteria"), Some(dir.join("Muse/01 - Hysteria.lrc")));
+        assert_eq!(find(&index, "Massive Attack", "Teardrop"), Some(dir.join("misc/track.lrc")));
+        assert_eq!(index.files.len(), 5);
+        assert_eq!(index.songs.len(), 4);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn changes_are_indexed() {
+        let (mut index, dir) = library("changes", &[
+            ("TOOL/Pneuma.lrc", "[00:01.00]We are born of one breath"),
+        ]);
+        assert!(find(&index, "TOOL", "Invincible").is_none());
+
+        fs::write(dir.join("TOOL/Invincible.lrc"), "[00:01.00]Long ago").unwrap();
+        fs::remove_file(dir.join("TOOL/Pneuma.lrc")).unwrap();
+        index.scan();
+        assert!(find(&index, "TOOL", "Invincible").is_some());
+        assert!(find(&index, "TOOL", "Pneuma").is_none());
+
+        // Retagged to another song
+        let path = dir.join("TOOL/Invincible.lrc");
+        fs::write(&path, "[ti:Descending]\n[00:01.00]Long ago").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        index.scan();
+        assert!(find(&index, "TOOL", "Invincible").is_none());
+        assert!(find(&index, "TOOL", "Descending").is_some());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
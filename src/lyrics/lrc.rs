@@ -0,0 +1,467 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Lyrics where every line has a start timestamp, as found in `.lrc` files.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SyncedLyrics {
+    pub metadata: LrcMetadata,
+    /// Sorted by `start`
+    pub lines: Vec<SyncedLine>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncedLine {
+    pub start: Duration,
+    /// Text without the word tags
+    pub text: String,
+    /// Empty unless the line has enhanced LRC word tags, e.g. `<00:12.50>word`
+    pub words: Vec<SyncedWord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncedWord {
+    /// `None` for text that is not preceded by a word tag, it starts with whatever is before it.
+    /// A word with empty text marks the end of the previous word.
+    pub start: Option<Duration>,
+    pub text: String,
+}
+
+/// ID tags of an LRC file, e.g. `[ar:Tool]`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LrcMetadata {
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub author: Option<String>,
+    pub length: Option<Duration>,
+    pub by: Option<String>,
+    /// In milliseconds, positive values make the lyrics appear sooner
+    pub offset: i64,
+    /// Tags we don't interpret, kept so that they can be written back
+    pub other: Vec<(String, String)>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LrcError {
+    #[error("No timestamped lines found")]
+    NoTimedLines,
+}
+
+impl SyncedLyrics {
+    /// Index of the line being sung at `position` of the track, `offset` tag is taken into account.
+    pub fn line_index_at(&self, position: Duration) -> Option<usize> {
+        let position = self.apply_offset(position);
+
+        match self.lines.partition_point(|line| line.start <= position) {
+            0 => None,
+            index => Some(index - 1),
+        }
+    }
+
+    pub fn apply_offset(&self, position: Duration) -> Duration {
+        let offset = Duration::from_millis(self.metadata.offset.unsigned_abs());
+
+        if self.metadata.offset >= 0 {
+            position + offset
+        } else {
+            position.saturating_sub(offset)
+        }
+    }
+
+    pub fn to_plain(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl SyncedLine {
+    /// Start of the word at `index`, untimed words fall back to the previous timing and finally to the line start
+    pub fn word_start(&self, index: usize) -> Duration {
+        self.words[..=index]
+            .iter()
+            .rev()
+            .find_map(|word| word.start)
+            .unwrap_or(self.start)
+    }
+
+    /// Index of the word being sung at `position`, `None` if the line has no word timing
+    pub fn word_index_at(&self, position: Duration) -> Option<usize> {
+        if self.words.is_empty() {
+            return None;
+        }
+
+        let index = (1..self.words.len())
+            .rev()
+            .find(|&index| self.word_start(index) <= position)
+            .unwrap_or(0);
+
+        Some(index)
+    }
+
+    fn parse(start: Duration, text: &str) -> Self {
+        let words = split_words(text);
+
+        let text = match words.is_empty() {
+            true => text.to_string(),
+            false => words
+                .iter()
+                .map(|word| word.text.as_str())
+                .collect::<String>()
+                .trim()
+                .to_string(),
+        };
+
+        SyncedLine { start, text, words }
+    }
+}
+
+impl fmt::Display for SyncedLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", format_timestamp(self.start))?;
+
+        if self.words.is_empty() {
+            return write!(f, "{}", self.text);
+        }
+
+        for word in &self.words {
+            if let Some(start) = word.start {
+                write!(f, "<{}>", format_timestamp(start))?;
+            }
+            write!(f, "{}", word.text)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SyncedLyrics {
+    type Err = LrcError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut lyrics = SyncedLyrics::default();
+
+        for line in input.lines() {
+            let (tags, text) = split_tags(line.trim());
+
+            let timestamps = tags
+                .iter()
+                .filter_map(|tag| parse_timestamp(tag))
+                .collect::<Vec<_>>();
+
+            if !timestamps.is_empty() {
+                for start in timestamps {
+                    lyrics.lines.push(SyncedLine::parse(start, text));
+                }
+            } else if text.is_empty() {
+                for tag in tags {
+                    lyrics.metadata.set_tag(tag);
+                }
+            }
+        }
+
+        if lyrics.lines.is_empty() {
+            return Err(LrcError::NoTimedLines);
+        }
+
+        // Stable so that lines with equal timestamps keep the file order
+        lyrics.lines.sort_by_key(|line| line.start);
+
+        Ok(lyrics)
+    }
+}
+
+impl fmt::Display for SyncedLyrics {
+    /// Writes the lyrics back in LRC format
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.metadata)?;
+
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl LrcMetadata {
+    fn set_tag(&mut self, tag: &str) {
+        let (key, value) = match tag.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim().to_string()),
+            None => return,
+        };
+
+        match key {
+            "ar" => self.artist = Some(value),
+            "ti" => self.title = Some(value),
+            "al" => self.album = Some(value),
+            "au" => self.author = Some(value),
+            "by" => self.by = Some(value),
+            "length" => self.length = parse_timestamp(&value),
+            "offset" => match value.trim_start_matches('+').parse() {
+                Ok(offset) => self.offset = offset,
+                Err(_) => self.other.push((key.to_string(), value)),
+            },
+            _ => self.other.push((key.to_string(), value)),
+        }
+    }
+}
+
+impl fmt::Display for LrcMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tags = [
+            ("ar", &self.artist),
+            ("ti", &self.title),
+            ("al", &self.album),
+            ("au", &self.author),
+            ("by", &self.by),
+        ];
+
+        for (key, value) in tags {
+            if let Some(value) = value {
+                writeln!(f, "[{}:{}]", key, value)?;
+            }
+        }
+        if let Some(length) = self.length {
+            writeln!(f, "[length:{}]", format_timestamp(length))?;
+        }
+        if self.offset != 0 {
+            writeln!(f, "[offset:{:+}]", self.offset)?;
+        }
+        for (key, value) in &self.other {
+            writeln!(f, "[{}:{}]", key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits the leading `[...]` tags of a line from the rest of the line
+fn split_tags(mut line: &str) -> (Vec<&str>, &str) {
+    let mut tags = Vec::new();
+
+    while let Some(rest) = line.strip_prefix('[') {
+        match rest.find(']') {
+            Some(end) => {
+                tags.push(&rest[..end]);
+                line = &rest[end + 1..];
+            }
+            None => break,
+        }
+    }
+
+    (tags, line.trim())
+}
+
+/// Splits a line on its `<mm:ss.xx>` word tags, empty if there are none
+fn split_words(mut text: &str) -> Vec<SyncedWord> {
+    let mut words = Vec::new();
+    let mut current = SyncedWord {
+        start: None,
+        text: String::new(),
+    };
+    let mut has_timing = false;
+
+    while !text.is_empty() {
+        let tag = text
+            .strip_prefix('<')
+            .and_then(|rest| rest.split_once('>'))
+            .and_then(|(tag, rest)| Some((parse_timestamp(tag)?, rest)));
+
+        match tag {
+            Some((start, rest)) => {
+                if current.start.is_some() || !current.text.is_empty() {
+                    words.push(current);
+                }
+                current = SyncedWord {
+                    start: Some(start),
+                    text: String::new(),
+                };
+                has_timing = true;
+                text = rest;
+            }
+            None => {
+                // Next tag candidate, at least one character is consumed
+                let first = text.chars().next().map_or(0, char::len_utf8);
+                let end = text[first..].find('<').map(|i| i + first).unwrap_or(text.len());
+                current.text.push_str(&text[..end]);
+                text = &text[end..];
+            }
+        }
+    }
+
+    if !has_timing {
+        return Vec::new();
+    }
+    if current.start.is_some() || !current.text.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+/// Parses `mm:ss.xx`, `mm:ss.xxx`, `mm:ss:xx` or `mm:ss`
+pub(super) fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let (minutes, rest) = timestamp.trim().split_once(':')?;
+
+    let (seconds, fraction) = match rest.find(['.', ':']) {
+        Some(index) => (&rest[..index], &rest[index + 1..]),
+        None => (rest, ""),
+    };
+
+    if minutes.is_empty()
+        || seconds.is_empty()
+        || !minutes.chars().all(|c| c.is_ascii_digit())
+        || !seconds.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+        || fraction.len() > 3
+    {
+        return None;
+    }
+
+    let minutes: u64 = minutes.parse().ok()?;
+    let seconds: u64 = seconds.parse().ok()?;
+    // "5" means 500 ms, "05" 50 ms and "005" 5 ms
+    let millis: u64 = match fraction.len() {
+        0 => 0,
+        len => fraction.parse::<u64>().ok()? * 10u64.pow(3 - len as u32),
+    };
+
+    if seconds >= 60 {
+        return None;
+    }
+
+    Some(Duration::from_millis((minutes * 60 + seconds) * 1000 + millis))
+}
+
+/// Uses centiseconds unless that would lose precision
+pub(super) fn format_timestamp(timestamp: Duration) -> String {
+    let millis = timestamp.as_millis();
+    let (minutes, seconds, millis) = (millis / 60_000, millis / 1000 % 60, millis % 1000);
+
+    if millis % 10 == 0 {
+        format!("{:02}:{:02}.{:02}", minutes, seconds, millis / 10)
+    } else {
+        format!("{:02}:{:02}.{:03}", minutes, seconds, millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{LrcError, SyncedLyrics};
+
+    const LRC: &str = "[ar:Tool]
+[ti:Fear Inoculum]
+[offset:+250]
+[re:SomeEditor]
+
+[00:12.00]Immunity, long overdue
+[00:17.50][01:02.125]Contagion, I exhale you
+[00:15.3]Naive, I opened up to you
+";
+
+    #[test]
+    fn parse_works() {
+        let lyrics: SyncedLyrics = LRC.parse().unwrap();
+
+        assert_eq!(lyrics.metadata.artist.as_deref(), Some("Tool"));
+        assert_eq!(lyrics.metadata.title.as_deref(), Some("Fear Inoculum"));
+        assert_eq!(lyrics.metadata.offset, 250);
+        assert_eq!(lyrics.metadata.other, vec![("re".to_string(), "SomeEditor".to_string())]);
+
+        let lines = lyrics
+            .lines
+            .iter()
+            .map(|line| (line.start.as_millis(), line.text.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines, vec![
+            (12_000, "Immunity, long overdue"),
+            (15_300, "Naive, I opened up to you"),
+            (17_500, "Contagion, I exhale you"),
+            (62_125, "Contagion, I exhale you"),
+        ]);
+    }
+
+    #[test]
+    fn plain_text_is_rejected() {
+        assert_eq!("[Verse 1]\nImmunity".parse::<SyncedLyrics>(), Err(LrcError::NoTimedLines));
+    }
+
+    #[test]
+    fn line_index_at_applies_offset() {
+        let lyrics: SyncedLyrics = LRC.parse().unwrap();
+
+        assert_eq!(lyrics.line_index_at(Duration::from_secs(5)), None);
+        assert_eq!(lyrics.line_index_at(Duration::from_millis(11_800)), Some(0));
+        assert_eq!(lyrics.line_index_at(Duration::from_secs(16)), Some(1));
+        assert_eq!(lyrics.line_index_at(Duration::from_secs(300)), Some(3));
+    }
+
+    #[test]
+    fn word_timing_works() {
+        let line = "[00:12.00]<00:12.00>Immunity, <00:12.80>long <00:13.40>overdue<00:14.00>";
+        let lyrics: SyncedLyrics = line.parse().unwrap();
+        let line = &lyrics.lines[0];
+
+        assert_eq!(line.text, "Immunity, long overdue");
+        assert_eq!(line.words.len(), 4);
+        assert_eq!(line.words[3].text, "");
+        assert_eq!(line.word_index_at(Duration::from_millis(12_500)), Some(0));
+        assert_eq!(line.word_index_at(Duration::from_millis(13_000)), Some(1));
+        assert_eq!(line.word_index_at(Duration::from_secs(20)), Some(3));
+        assert_eq!(lyrics.to_string(), "[00:12.00]<00:12.00>Immunity, <00:12.80>long <00:13.40>overdue<00:14.00>\n");
+    }
+
+    #[test]
+    fn untimed_words_fall_back_to_line_timing() {
+        let lyrics: SyncedLyrics = "[00:10.00]Exhale, <00:11.00>expel <3".parse().unwrap();
+        let line = &lyrics.lines[0];
+
+        assert_eq!(line.text, "Exhale, expel <3");
+        assert_eq!(line.words[0].start, None);
+        assert_eq!(line.word_start(0), Duration::from_secs(10));
+        assert_eq!(line.word_start(1), Duration::from_secs(11));
+        assert_eq!(lyrics.to_string(), "[00:10.00]Exhale, <00:11.00>expel <3\n");
+
+        let plain: SyncedLyrics = "[00:10.00]Recast my tale".parse().unwrap();
+        assert!(plain.lines[0].words.is_empty());
+        assert_eq!(plain.lines[0].word_index_at(Duration::from_secs(11)), None);
+    }
+
+    #[test]
+    fn non_ascii_words_work() {
+        let lyrics: SyncedLyrics = "[00:10.00]Ümit <00:11.00>çok <00:12.00>güzel".parse().unwrap();
+        let line = &lyrics.lines[0];
+
+        assert_eq!(line.text, "Ümit çok güzel");
+        assert_eq!(line.words.len(), 3);
+        assert_eq!(line.words[1].text, "çok ");
+        assert_eq!(line.word_start(0), Duration::from_secs(10));
+
+        let plain: SyncedLyrics = "[00:10.00]Ümit".parse().unwrap();
+        assert_eq!(plain.lines[0].text, "Ümit");
+    }
+
+    #[test]
+    fn serialize_round_trips() {
+        let lyrics: SyncedLyrics = LRC.parse().unwrap();
+        let written = lyrics.to_string();
+
+        assert!(written.contains("[01:02.125]Contagion, I exhale you\n"));
+        assert!(written.contains("[00:15.30]Naive, I opened up to you\n"));
+        assert_eq!(written.parse::<SyncedLyrics>().unwrap(), lyrics);
+
+        let lyrics: SyncedLyrics = format!("[length:03:25.50]\n{}", LRC).parse().unwrap();
+        let written = lyrics.to_string();
+
+        assert!(written.contains("[length:03:25.50]\n"));
+        assert_eq!(written.parse::<SyncedLyrics>().unwrap(), lyrics);
+    }
+}
@@ -0,0 +1,238 @@
+use futures::future::BoxFuture;
+use reqwest::{Client, ClientBuilder, StatusCode};
+use serde_derive::Deserialize;
+
+use crate::{configs::NETWORK_TIMEOUT_DURATION, player::metadata::TrackMetadata};
+
+use super::{lrc::SyncedLyrics, normalize::{self, SongQuery}, Lyrics, LyricsError, LyricsFetcher, LyricsResponse, LyricsResult};
+
+pub const DEFAULT_BASE_URL: &'static str = "https://lrclib.net";
+static PROVIDER_NAME: &'static str = "LRCLIB";
+static USER_AGENT: &'static str = concat!("SyncLyrics ", env!("CARGO_PKG_VERSION"), " (https://github.com/onsah/SyncLyrics)");
+/// Matched by artist, title and album only, the length may differ
+const UNTIMED_MATCH_CONFIDENCE: f32 = 0.8;
+/// Query variants tried before giving up
+const MAX_QUERIES: usize = 2;
+
+/// Client of the LRCLIB `/api/get` endpoint, or a server with the same api
+pub struct LrcLib {
+    client: Client,
+    base_url: String,
+}
+
+/// A track from `/api/get`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LrcLibTrack {
+    track_name: String,
+    artist_name: String,
+    #[serde(default)]
+    instrumental: bool,
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+}
+
+impl LrcLib {
+    pub fn new(base_url: &str) -> Self {
+        LrcLib {
+            client: ClientBuilder::new()
+                .timeout(NETWORK_TIMEOUT_DURATION)
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Tries the first few query variants, the api needs an artist
+    async fn fetch_lyrics(&self, metadata: &TrackMetadata) -> LyricsResult {
+        let queries = normalize::query_variants(metadata)
+            .into_iter()
+            .filter(|query| query.artist.is_some())
+            .take(MAX_QUERIES);
+
+        for query in queries {
+            if let Some(response) = self.get_track(&query, metadata).await? {
+                return Ok(response);
+            }
+        }
+
+        Err(Self::not_found(metadata))
+    }
+
+    /// `None` if LRCLIB doesn't have the song
+    async fn get_track(&self, song_query: &SongQuery, metadata: &TrackMetadata) -> LyricsResult<Option<LyricsResponse>> {
+        let url = format!("{}/api/get", self.base_url);
+
+        let mut query = vec![
+            ("artist_name", song_query.artist.clone().unwrap_or_default()),
+            ("track_name", song_query.title.clone()),
+        ];
+        if let Some(album) = &metadata.album {
+            query.push(("album_name", album.clone()));
+        }
+        if let Some(length) = metadata.length {
+            query.push(("duration", length.as_secs().to_string()));
+        }
+
+        let request = self.client.get(&url).query(&query).build()?;
+        let source_url = request.url().to_string();
+        let resp = self.client.execute(request).await?;
+
+        // Missing or incomplete parameters are a bad request
+        if resp.status() == StatusCode::NOT_FOUND || resp.status() == StatusCode::BAD_REQUEST {
+            println!("lrclib: No {} by {:?}", song_query.title, song_query.artist);
+            return Ok(None);
+        }
+
+        let track: LrcLibTrack = resp.error_for_status()?.json().await?;
+
+        let confidence = if metadata.length.is_some() { 1.0 } else { UNTIMED_MATCH_CONFIDENCE };
+
+        Ok(Self::to_response(track, Some(source_url), confidence))
+    }
+
+    /// Synced lyrics if there are any, `None` if the track has no lyrics at all
+    fn to_response(track: LrcLibTrack, source_url: Option<String>, confidence: f32) -> Option<LyricsResponse> {
+        let synced = track
+            .synced_lyrics
+            .as_deref()
+            .and_then(|synced| match synced.parse::<SyncedLyrics>() {
+                Ok(synced) => Some(synced),
+                Err(error) => {
+                    println!("lrclib: Ignoring the synced lyrics of {}: {}", track.track_name, error);
+                    None
+                }
+            });
+        let plain = track
+            .plain_lyrics
+            .filter(|plain| !plain.trim().is_empty());
+
+        let lyrics = match (synced, plain) {
+            (Some(synced), _) => Lyrics::Synced(synced),
+            (None, Some(plain)) => Lyrics::Plain(plain),
+            (None, None) if track.instrumental => Lyrics::Plain("[Instrumental]".to_string()),
+            (None, None) => return None,
+        };
+
+        Some(LyricsResponse {
+            artist: track.artist_name,
+            track: track.track_name,
+            lyrics,
+            cover_art: Vec::new(),
+            provider: PROVIDER_NAME.to_string(),
+            source_url,
+            confidence,
+        })
+    }
+
+    fn not_found(metadata: &TrackMetadata) -> LyricsError {
+        LyricsError::SongNotFound {
+            song_name: metadata.title.clone(),
+            artist: metadata.primary_artist().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+impl LyricsFetcher for LrcLib {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn get_lyrics<'a>(&'a self, metadata: &'a TrackMetadata) -> BoxFuture<'a, LyricsResult> {
+        Box::pin(self.fetch_lyrics(metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        lyrics::{
+            stub_server::{StubResponse, StubServer},
+            Lyrics, LyricsError, LyricsFetcher,
+        },
+        player::metadata::TrackMetadata,
+    };
+
+    use super::{LrcLib, LrcLibTrack};
+
+    fn track(json: &str) -> LrcLibTrack {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn synced_lyrics_are_preferred() {
+        let response = LrcLib::to_response(track(r#"{
+            "id": 3396226,
+            "trackName": "Pneuma",
+            "artistName": "TOOL",
+            "albumName": "Fear Inoculum",
+            "duration": 713,
+            "instrumental": false,
+            "plainLyrics": "We are born of one breath\nOne word",
+            "syncedLyrics": "[01:52.30] We are born of one breath\n[01:58.91] One word"
+        }"#), None, 1.0).unwrap();
+
+        match response.lyrics {
+            Lyrics::Synced(synced) => assert_eq!(synced.lines.len(), 2),
+            other => panic!("Expected synced lyrics, got {:?}", other),
+        }
+        assert_eq!(response.provider, "LRCLIB");
+        assert_eq!(response.track, "Pneuma");
+    }
+
+    #[test]
+    fn plain_lyrics_are_the_fallback() {
+        let response = LrcLib::to_response(track(r#"{
+            "trackName": "Pneuma",
+            "artistName": "TOOL",
+            "plainLyrics": "We are born of one breath",
+            "syncedLyrics": null
+        }"#), None, 1.0).unwrap();
+        assert_eq!(response.lyrics.to_plain(), "We are born of one breath");
+
+        let instrumental = LrcLib::to_response(track(r#"{
+            "trackName": "Legion Inoculant",
+            "artistName": "TOOL",
+            "instrumental": true,
+            "plainLyrics": null,
+            "syncedLyrics": null
+        }"#), None, 1.0).unwrap();
+        assert_eq!(instrumental.lyrics.to_plain(), "[Instrumental]");
+
+        let empty = LrcLib::to_response(track(r#"{
+            "trackName": "Pneuma",
+            "artistName": "TOOL",
+            "plainLyrics": "",
+            "syncedLyrics": null
+        }"#), None, 1.0);
+        assert!(empty.is_none());
+    }
+
+    #[tokio::test]
+    async fn lyrics_are_fetched() {
+        let server = StubServer::start(vec![("/api/get", StubResponse::fixture("lrclib/get.json"))]);
+        let lrclib = LrcLib::new(&format!("{}/", server.base_url()));
+
+        let metadata = TrackMetadata {
+            title: "Pneuma".to_string(),
+            artists: vec!["TOOL".to_string()],
+            album: Some("Fear Inoculum".to_string()),
+            length: Some(Duration::from_secs(713)),
+            ..TrackMetadata::default()
+        };
+        let response = lrclib.get_lyrics(&metadata).await.unwrap();
+
+        assert!(response.lyrics.is_synced());
+        assert_eq!(response.confidence, 1.0);
+        assert_eq!(server.requests(), vec!["/api/get?artist_name=TOOL&track_name=Pneuma&album_name=Fear+Inoculum&duration=713"]);
+        assert_eq!(response.source_url, Some(format!("{}{}", server.base_url(), server.requests()[0])));
+
+        // Not found
+        let server = StubServer::start(Vec::new());
+        let not_found = LrcLib::new(server.base_url()).get_lyrics(&metadata).await;
+        assert!(matches!(not_found, Err(LyricsError::SongNotFound { .. })));
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use crate::player::metadata::TrackMetadata;
+
+use super::normalize::{normalize_artist, normalize_title};
+
+/// Lengths closer than this are the same song
+const SAME_LENGTH_TOLERANCE: Duration = Duration::from_secs(3);
+/// Lengths this far apart are different songs
+const DIFFERENT_LENGTH: Duration = Duration::from_secs(30);
+/// Artist score of a result without artists, a matching title alone isn't enough
+const UNKNOWN_ARTIST_SCORE: f32 = 0.5;
+
+/// What is known of a song, to tell whether a search result is the song being played
+#[derive(Debug, Clone, Default)]
+pub struct SongDescription<'a> {
+    pub title: &'a str,
+    pub artists: Vec<&'a str>,
+    pub length: Option<Duration>,
+}
+
+impl<'a> From<&'a TrackMetadata> for SongDescription<'a> {
+    fn from(metadata: &'a TrackMetadata) -> Self {
+        SongDescription {
+            title: &metadata.title,
+            artists: metadata.artists.iter().map(|artist| artist.as_str()).collect(),
+            length: metadata.length,
+        }
+    }
+}
+
+/// From 0 to 1, how likely `found` is the `wanted` song.
+/// Titles and artists are compared without versions and featured artists, lengths only if both are known.
+pub fn match_score(wanted: &SongDescription, found: &SongDescription) -> f32 {
+    let title = similarity(&normalize_title(wanted.title), &normalize_title(found.title));
+
+    let artist = if wanted.artists.is_empty() {
+        // Some players don't tell the artist
+        1.0
+    } else {
+        wanted
+            .artists
+            .iter()
+            .flat_map(|wanted| found.artists.iter().map(move |found| similarity(&normalize_artist(wanted), &normalize_artist(found))))
+            .reduce(f32::max)
+            .unwrap_or(UNKNOWN_ARTIST_SCORE)
+    };
+
+    let length = match (wanted.length, found.length) {
+        (Some(wanted), Some(found)) => length_similarity(wanted, found),
+        _ => 1.0,
+    };
+
+    title * artist * length
+}
+
+/// 1 for equal texts, 0 for completely different ones, based on the edit distance
+fn similarity(a: &str, b: &str) -> f32 {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    1.0 - edit_distance(&a, &b) as f32 / longest as f32
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == b_char { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+fn length_similarity(a: Duration, b: Duration) -> f32 {
+    let difference = a.abs_diff(b);
+
+    if difference <= SAME_LENGTH_TOLERANCE {
+        1.0
+    } else if difference >= DIFFERENT_LENGTH {
+        0.0
+    } else {
+        1.0 - (difference - SAME_LENGTH_TOLERANCE).as_secs_f32()
+            / (DIFFERENT_LENGTH - SAME_LENGTH_TOLERANCE).as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{match_score, SongDescription};
+
+    fn song(title: &'static str, artist: &'static str, length: Option<u64>) -> SongDescription<'static> {
+        SongDescription {
+            title,
+            artists: vec![artist],
+            length: length.map(Duration::from_secs),
+        }
+    }
+
+    #[test]
+    fn scores() {
+        let wanted = song("HUMBLE.", "Kendrick Lamar", None);
+
+        assert_eq!(match_score(&wanted, &song("Humble", "kendrick lamar", None)), 1.0);
+        // A cover
+        assert!(match_score(&wanted, &song("HUMBLE.", "Boyce Avenue", None)) < 0.5);
+        // A translation page
+        assert!(match_score(&wanted, &song("Kendrick Lamar - HUMBLE. (Traducción al Español)", "Genius Traducciones al Español", None)) < 0.5);
+        // Other versions of the song
+        assert_eq!(match_score(&wanted, &song("HUMBLE. - Radio Edit", "Kendrick Lamar feat. Someone", None)), 1.0);
+        // Typos are fine
+        assert!(match_score(&wanted, &song("HUMBL", "Kendrick Lamarr", None)) > 0.7);
+
+        // The artist isn't known
+        let untold = SongDescription { artists: Vec::new(), ..wanted.clone() };
+        assert_eq!(match_score(&untold, &song("HUMBLE.", "Boyce Avenue", None)), 1.0);
+
+        // The result doesn't tell the artist
+        let anonymous = SongDescription { artists: Vec::new(), ..song("HUMBLE.", "", None) };
+        assert!(match_score(&wanted, &anonymous) < 0.6);
+    }
+
+    #[test]
+    fn length_is_compared_when_known() {
+        let wanted = song("Pneuma", "TOOL", Some(713));
+
+        assert_eq!(match_score(&wanted, &song("Pneuma", "TOOL", Some(715))), 1.0);
+        assert_eq!(match_score(&wanted, &song("Pneuma", "TOOL", None)), 1.0);
+        assert!(match_score(&wanted, &song("Pneuma", "TOOL", Some(729))) < 0.7);
+        assert_eq!(match_score(&wanted, &song("Pneuma", "TOOL", Some(650))), 0.0);
+    }
+}
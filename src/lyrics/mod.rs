@@ -1,11 +1,48 @@
+use std::path::PathBuf;
+
+use futures::future::BoxFuture;
 use serde_derive::{Deserialize, Serialize};
 use thiserror::Error;
 use reqwest;
 
+pub mod cache;
+pub mod embedded;
+pub mod external;
+pub mod file_watcher;
 pub mod genius;
+pub mod library;
+pub mod lrc;
+pub mod lrclib;
+pub mod matching;
+pub mod normalize;
+pub mod registry;
+pub mod sections;
+pub mod sidecar;
+#[cfg(test)]
+#[path = "../../tests/support/stub_server.rs"]
+mod stub_server;
+
+use lrc::SyncedLyrics;
+use sections::LyricsSection;
+
+use crate::player::metadata::TrackMetadata;
+
+/// A source of lyrics, boxed into a `ProviderRegistry`
+pub trait LyricsFetcher: Send + Sync {
+    /// Shown to the user and used in the provider priority setting
+    fn name(&self) -> &str;
+
+    /// Local lyrics are asked before the cache and never cached
+    fn is_local(&self) -> bool {
+        false
+    }
 
-pub trait LyricsFetcher {
-    fn get_lyrics(&self, song_title: &str, artist: &str) -> LyricsResult;
+    /// Files whose changes should refetch the lyrics of `metadata`
+    fn watched_files(&self, _metadata: &TrackMetadata) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    fn get_lyrics<'a>(&'a self, metadata: &'a TrackMetadata) -> BoxFuture<'a, LyricsResult>;
 }
 
 pub type LyricsResult<T = LyricsResponse> = Result<T, LyricsError>;
@@ -14,8 +51,75 @@ pub type LyricsResult<T = LyricsResponse> = Result<T, LyricsError>;
 pub struct LyricsResponse {
     pub artist: String,
     pub track: String,
-    pub lyrics: String,
+    pub lyrics: Lyrics,
     pub cover_art: Vec<u8>,
+    /// Name of the service the lyrics are from
+    pub provider: String,
+    pub source_url: Option<String>,
+    /// From 0 to 1, how sure the provider is that the lyrics are of the requested song
+    pub confidence: f32,
+}
+
+/// Where the lyrics shown to the user came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LyricsOrigin {
+    Network,
+    Cache,
+    /// From the cache while there is no network connection
+    Offline,
+    /// From files on this computer
+    Local,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Lyrics {
+    Plain(String),
+    Synced(SyncedLyrics),
+    /// Plain lyrics split at their `[Verse]`, `[Chorus]`... headers
+    Sectioned(Vec<LyricsSection>),
+}
+
+impl Lyrics {
+    /// Synced if `text` is in the LRC format, plain otherwise.
+    /// `None` if there is no text.
+    pub fn from_text(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(match text.parse::<SyncedLyrics>() {
+            Ok(synced) => Lyrics::Synced(synced),
+            Err(_) => Lyrics::Plain(text.to_string()),
+        })
+    }
+
+    /// Sectioned if `text` has section headers, plain otherwise
+    pub fn from_sectioned_text(text: &str) -> Self {
+        let sections = LyricsSection::parse_all(text);
+
+        if sections.iter().any(|section| section.name.is_some()) {
+            Lyrics::Sectioned(sections)
+        } else {
+            Lyrics::Plain(text.to_string())
+        }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        matches!(self, Lyrics::Synced(_))
+    }
+
+    pub fn to_plain(&self) -> String {
+        match self {
+            Lyrics::Plain(lyrics) => lyrics.clone(),
+            Lyrics::Synced(synced) => synced.to_plain(),
+            Lyrics::Sectioned(sections) => sections
+                .iter()
+                .map(|section| section.to_plain())
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -27,4 +131,8 @@ pub enum LyricsError {
     SpotifyClosed,
     #[error("Song {song_name} by {artist} is not found")]
     SongNotFound { song_name: String, artist: String },
+    #[error("No lyrics provider is enabled")]
+    NoProvider,
+    #[error("{provider} failed: {reason}")]
+    ProviderFailed { provider: String, reason: String },
 }
\ No newline at end of file
@@ -0,0 +1,443 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::player::metadata::TrackMetadata;
+
+/// Words in a title suffix that mark another version of the same song, e.g. `- 2011 Remaster`
+const VERSION_WORDS: [&'static str; 20] = [
+    "acoustic", "anniversary", "bonus", "clean", "deluxe", "demo", "edit", "explicit", "live", "mix",
+    "mono", "radio", "recorded", "remaster", "remastered", "remix", "session", "single", "stereo", "version",
+];
+/// Words that start a list of featured artists
+const FEATURE_WORDS: [&'static str; 4] = ["feat", "ft", "featuring", "with"];
+/// Separators of artist lists, e.g. `Daft Punk, Pharrell Williams & Nile Rodgers`
+const ARTIST_SEPARATORS: [&'static str; 5] = [", ", " & ", "; ", " / ", " x "];
+/// Letters that don't decompose into a base letter and a diacritic
+const FOLDED_LETTERS: [(char, &'static str); 14] = [
+    ('ß', "ss"), ('æ', "ae"), ('Æ', "AE"), ('œ', "oe"), ('Œ', "OE"), ('ø', "o"), ('Ø', "O"),
+    ('ł', "l"), ('Ł', "L"), ('đ', "d"), ('Đ', "D"), ('ı', "i"), ('þ', "th"), ('Þ', "TH"),
+];
+
+/// A title without its version and featured artist suffixes
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CleanTitle {
+    pub title: String,
+    /// Artists from `(feat. ...)`
+    pub featured: Vec<String>,
+}
+
+/// A way of searching for a song, from `query_variants`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SongQuery {
+    pub title: String,
+    pub artist: Option<String>,
+}
+
+impl SongQuery {
+    /// Title and artist as a single search text
+    pub fn search_text(&self) -> String {
+        match &self.artist {
+            Some(artist) => format!("{} {}", self.title, artist),
+            None => self.title.clone(),
+        }
+    }
+}
+
+/// Removes the suffixes that are not part of the song name:
+/// `Song - 2011 Remaster`, `Song - Live at Wembley`, `Song (Radio Edit)`, `Song (feat. X)`, `Song feat. X`
+pub fn clean_title(title: &str) -> CleanTitle {
+    let mut title = title.trim().to_string();
+    let mut featured = Vec::new();
+
+    while let Some((rest, suffix)) = split_suffix(&title) {
+        let rest = rest.trim_end();
+        // The title itself may look like a suffix
+        if rest.is_empty() {
+            break;
+        }
+
+        match feature_list(suffix) {
+            Some(artists) => featured.extend(split_artists(artists)),
+            None if is_version(suffix) => (),
+            None => break,
+        }
+        title = rest.to_string();
+    }
+
+    // Unbracketed features, `Song feat. X`
+    if let Some((rest, artists)) = split_inline_feature(&title) {
+        featured.extend(split_artists(artists));
+        title = rest.to_string();
+    }
+
+    CleanTitle { title, featured }
+}
+
+/// The artist without the featured ones and the featured ones, `A feat. B & C` is `A` and `[B, C]`
+pub fn split_featured(artist: &str) -> (String, Vec<String>) {
+    match split_inline_feature(artist.trim()) {
+        Some((main, featured)) => (main.to_string(), split_artists(featured)),
+        None => (artist.trim().to_string(), Vec::new()),
+    }
+}
+
+/// Artists of a list, `A, B & C` is `[A, B, C]`.
+/// Some band names look like lists, so the whole name should be tried first.
+pub fn split_artists(artists: &str) -> Vec<String> {
+    let mut split = vec![artists.to_string()];
+
+    for separator in &ARTIST_SEPARATORS {
+        split = split
+            .iter()
+            .flat_map(|artist| split_ignore_case(artist, separator))
+            .collect();
+    }
+
+    split
+        .into_iter()
+        .map(|artist| artist.trim().to_string())
+        .filter(|artist| !artist.is_empty())
+        .collect()
+}
+
+/// Removes diacritics, replaces letters like `ß` and typographic punctuation with ASCII
+pub fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(|c| {
+            let folded = match FOLDED_LETTERS.iter().find(|(letter, _)| *letter == c) {
+                Some((_, folded)) => folded,
+                None => match c {
+                    '‘' | '’' | '`' | '´' => "'",
+                    '“' | '”' | '„' => "\"",
+                    '‐' | '‑' | '‒' | '–' | '—' => "-",
+                    '…' => "...",
+                    _ => return vec![c],
+                },
+            };
+            folded.chars().collect()
+        })
+        .collect()
+}
+
+/// Folded lowercase words without punctuation, for comparing names.
+/// `&` is `and` and apostrophes are dropped so that `Don't` is `dont`.
+pub fn normalize(text: &str) -> String {
+    fold(text)
+        .to_lowercase()
+        .replace('&', " and ")
+        .replace('\'', "")
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Normalized title without the version and featured artists
+pub fn normalize_title(title: &str) -> String {
+    normalize(&clean_title(title).title)
+}
+
+/// Normalized artist without the featured artists
+pub fn normalize_artist(artist: &str) -> String {
+    normalize(&split_featured(artist).0)
+}
+
+/// Searches for the song from the most to the least specific, without duplicates:
+/// the clean title with the main artist, folded to ASCII, the first artist of a list,
+/// the title and artist as they are, and finally the clean title alone
+pub fn query_variants(metadata: &TrackMetadata) -> Vec<SongQuery> {
+    let title = clean_title(&metadata.title).title;
+    let artist = metadata.primary_artist().map(|artist| split_featured(artist).0);
+
+    let mut variants = vec![
+        SongQuery {
+            title: title.clone(),
+            artist: artist.clone(),
+        },
+        SongQuery {
+            title: fold(&title),
+            artist: artist.as_deref().map(fold),
+        },
+    ];
+    if let Some(first_artist) = artist.as_deref().and_then(|artist| split_artists(artist).into_iter().next()) {
+        variants.push(SongQuery {
+            title: title.clone(),
+            artist: Some(first_artist),
+        });
+    }
+    variants.push(SongQuery {
+        title: metadata.title.trim().to_string(),
+        artist: metadata.primary_artist().map(|artist| artist.trim().to_string()),
+    });
+    variants.push(SongQuery { title, artist: None });
+
+    let mut unique: Vec<SongQuery> = Vec::new();
+    for variant in variants {
+        if !variant.title.is_empty() && !unique.contains(&variant) {
+            unique.push(variant);
+        }
+    }
+    unique
+}
+
+/// Every artist of the song without duplicates, the main ones first:
+/// the artists without features, the members of artist lists, and the featured artists of the artists and title
+pub fn credited_artists(metadata: &TrackMetadata) -> Vec<String> {
+    let mut main = Vec::new();
+    let mut featured = Vec::new();
+    for artist in &metadata.artists {
+        let (artist, artist_featured) = split_featured(artist);
+        // The whole name first, some band names look like lists
+        let members = split_artists(&artist);
+        main.push(artist);
+        main.extend(members);
+        featured.extend(artist_featured);
+    }
+    featured.extend(clean_title(&metadata.title).featured);
+
+    let mut unique: Vec<String> = Vec::new();
+    for artist in main.into_iter().chain(featured) {
+        if !artist.is_empty() && !unique.iter().any(|known| normalize_artist(known) == normalize_artist(&artist)) {
+            unique.push(artist);
+        }
+    }
+    unique
+}
+
+/// The text before a trailing `(...)`, `[...]` or ` - ...` and the suffix without its brackets or dash
+fn split_suffix(title: &str) -> Option<(&str, &str)> {
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        if title.ends_with(close) {
+            let start = title.rfind(open)?;
+            return Some((&title[..start], &title[start + 1..title.len() - 1]));
+        }
+    }
+
+    let start = title.rfind(" - ")?;
+    Some((&title[..start], &title[start + 3..]))
+}
+
+/// Artists of a suffix like `feat. X & Y`
+fn feature_list(suffix: &str) -> Option<&str> {
+    let suffix = suffix.trim();
+    let (first_word, rest) = suffix.split_once(' ')?;
+    let first_word = first_word.trim_end_matches('.');
+
+    FEATURE_WORDS
+        .iter()
+        .any(|word| first_word.eq_ignore_ascii_case(word))
+        .then(|| rest.trim())
+}
+
+fn is_version(suffix: &str) -> bool {
+    let normalized = normalize(suffix);
+    let mut words = normalized.split(' ');
+
+    // `- From "Frozen"`
+    if words.clone().next() == Some("from") {
+        return true;
+    }
+    words.any(|word| VERSION_WORDS.contains(&word))
+}
+
+/// `Song feat. X` is `Song` and `X`, `with` is only a feature marker in brackets
+fn split_inline_feature(text: &str) -> Option<(&str, &str)> {
+    let lowercase = text.to_lowercase();
+    // Lowercasing may change the length of other letters
+    if lowercase.len() != text.len() {
+        return None;
+    }
+
+    [" feat. ", " feat ", " ft. ", " ft ", " featuring "]
+        .iter()
+        .filter_map(|marker| lowercase.find(marker).map(|start| (start, marker.len())))
+        .min()
+        .map(|(start, length)| (text[..start].trim_end(), text[start + length..].trim()))
+        .filter(|(main, featured)| !main.is_empty() && !featured.is_empty())
+}
+
+fn split_ignore_case(text: &str, separator: &str) -> Vec<String> {
+    let lowercase = text.to_lowercase();
+    if lowercase.len() != text.len() {
+        return vec![text.to_string()];
+    }
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while let Some(found) = lowercase[start..].find(separator) {
+        parts.push(text[start..start + found].to_string());
+        start += found + separator.len();
+    }
+    parts.push(text[start..].to_string());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::player::metadata::TrackMetadata;
+
+    use super::{clean_title, credited_artists, fold, normalize, normalize_artist, normalize_title, query_variants, split_artists, split_featured, SongQuery};
+
+    #[test]
+    fn titles_are_cleaned() {
+        let cases = [
+            ("Pneuma", "Pneuma", vec![]),
+            ("  Pneuma  ", "Pneuma", vec![]),
+            ("Bohemian Rhapsody - 2011 Remaster", "Bohemian Rhapsody", vec![]),
+            ("Bohemian Rhapsody - Remastered 2011", "Bohemian Rhapsody", vec![]),
+            ("Bohemian Rhapsody (2011 Remaster)", "Bohemian Rhapsody", vec![]),
+            ("Bohemian Rhapsody - Live Aid", "Bohemian Rhapsody", vec![]),
+            ("Don't Stop Me Now - Live at Wembley '86", "Don't Stop Me Now", vec![]),
+            ("Hey Jude - Remastered 2015 - Mono", "Hey Jude", vec![]),
+            ("Smells Like Teen Spirit [Radio Edit]", "Smells Like Teen Spirit", vec![]),
+            ("Creep (Acoustic)", "Creep", vec![]),
+            ("Let It Go - From \"Frozen\"", "Let It Go", vec![]),
+            ("Get Lucky (feat. Pharrell Williams & Nile Rodgers)", "Get Lucky", vec!["Pharrell Williams", "Nile Rodgers"]),
+            ("Get Lucky (Radio Edit) [feat. Pharrell Williams]", "Get Lucky", vec!["Pharrell Williams"]),
+            ("Stay (with Justin Bieber)", "Stay", vec!["Justin Bieber"]),
+            ("Sicko Mode ft. Drake", "Sicko Mode", vec!["Drake"]),
+            ("Love Song - feat. Someone", "Love Song", vec!["Someone"]),
+            ("HUMBLE.", "HUMBLE.", vec![]),
+            // Suffixes that are part of the name
+            ("Stairway to Heaven (Part 1)", "Stairway to Heaven (Part 1)", vec![]),
+            ("Paranoid Android - Radiohead Cover - Part 2", "Paranoid Android - Radiohead Cover - Part 2", vec![]),
+            ("Wish You Were Here - Pink Floyd", "Wish You Were Here - Pink Floyd", vec![]),
+            // Only a suffix
+            ("(Live)", "(Live)", vec![]),
+            ("Live - Remastered", "Live", vec![]),
+        ];
+
+        for (title, clean, featured) in cases {
+            let cleaned = clean_title(title);
+            assert_eq!(cleaned.title, clean, "{}", title);
+            assert_eq!(cleaned.featured, featured, "{}", title);
+        }
+    }
+
+    #[test]
+    fn artists_are_split() {
+        let featured_cases = [
+            ("Daft Punk", "Daft Punk", vec![]),
+            ("Daft Punk feat. Pharrell Williams", "Daft Punk", vec!["Pharrell Williams"]),
+            ("Travis Scott Ft. Drake & Swae Lee", "Travis Scott", vec!["Drake", "Swae Lee"]),
+            ("Earth, Wind & Fire", "Earth, Wind & Fire", vec![]),
+        ];
+        for (artist, main, featured) in featured_cases {
+            assert_eq!(split_featured(artist), (main.to_string(), featured.iter().map(|a| a.to_string()).collect()), "{}", artist);
+        }
+
+        let list_cases = [
+            ("Daft Punk", vec!["Daft Punk"]),
+            ("Daft Punk, Pharrell Williams & Nile Rodgers", vec!["Daft Punk", "Pharrell Williams", "Nile Rodgers"]),
+            ("Simon & Garfunkel", vec!["Simon", "Garfunkel"]),
+            ("Marshmello x Bastille", vec!["Marshmello", "Bastille"]),
+            ("Beyoncé; JAY-Z", vec!["Beyoncé", "JAY-Z"]),
+        ];
+        for (artists, split) in list_cases {
+            assert_eq!(split_artists(artists), split, "{}", artists);
+        }
+    }
+
+    #[test]
+    fn text_is_folded() {
+        let cases = [
+            ("Beyoncé", "Beyonce"),
+            ("Sigur Rós", "Sigur Ros"),
+            ("Motörhead", "Motorhead"),
+            ("Mötley Crüe", "Motley Crue"),
+            ("Straße", "Strasse"),
+            ("Røyksopp", "Royksopp"),
+            ("Œuvre", "OEuvre"),
+            ("Don’t Stop", "Don't Stop"),
+            ("“Quoted” – Title…", "\"Quoted\" - Title..."),
+            ("Ünïcödé", "Unicode"),
+            ("東京", "東京"),
+        ];
+
+        for (text, folded) in cases {
+            assert_eq!(fold(text), folded, "{}", text);
+        }
+    }
+
+    #[test]
+    fn names_are_normalized() {
+        let cases = [
+            ("HUMBLE.", "humble"),
+            ("  Don't  Stop Me Now ", "dont stop me now"),
+            ("Don’t Stop Me Now", "dont stop me now"),
+            ("Simon & Garfunkel", "simon and garfunkel"),
+            ("AC/DC", "ac dc"),
+            ("Beyoncé", "beyonce"),
+            ("P!nk", "p nk"),
+            ("", ""),
+        ];
+        for (text, normalized) in cases {
+            assert_eq!(normalize(text), normalized, "{}", text);
+        }
+
+        assert_eq!(normalize_title("Bohemian Rhapsody - Remastered 2011"), "bohemian rhapsody");
+        assert_eq!(normalize_title("Get Lucky (feat. Pharrell Williams)"), normalize_title("get lucky"));
+        assert_eq!(normalize_artist("Daft Punk feat. Pharrell Williams"), "daft punk");
+    }
+
+    #[test]
+    fn query_variants_are_ordered() {
+        fn query(title: &str, artist: Option<&str>) -> SongQuery {
+            SongQuery {
+                title: title.to_string(),
+                artist: artist.map(|artist| artist.to_string()),
+            }
+        }
+
+        let metadata = TrackMetadata {
+            title: "Déjà Vu - 2011 Remaster".to_string(),
+            artists: vec!["Crosby, Stills, Nash & Young feat. Someone".to_string()],
+            ..TrackMetadata::default()
+        };
+        assert_eq!(query_variants(&metadata), vec![
+            query("Déjà Vu", Some("Crosby, Stills, Nash & Young")),
+            query("Deja Vu", Some("Crosby, Stills, Nash & Young")),
+            query("Déjà Vu", Some("Crosby")),
+            query("Déjà Vu - 2011 Remaster", Some("Crosby, Stills, Nash & Young feat. Someone")),
+            query("Déjà Vu", None),
+        ]);
+        assert_eq!(query_variants(&metadata)[0].search_text(), "Déjà Vu Crosby, Stills, Nash & Young");
+
+        // Duplicates are left out
+        let plain = TrackMetadata {
+            title: "Pneuma".to_string(),
+            artists: vec!["TOOL".to_string()],
+            ..TrackMetadata::default()
+        };
+        assert_eq!(query_variants(&plain), vec![query("Pneuma", Some("TOOL")), query("Pneuma", None)]);
+
+        let no_artist = TrackMetadata {
+            title: "Pneuma (Live)".to_string(),
+            ..TrackMetadata::default()
+        };
+        assert_eq!(query_variants(&no_artist), vec![query("Pneuma", None), query("Pneuma (Live)", None)]);
+    }
+
+    #[test]
+    fn credited_artists_are_listed() {
+        let cases = [
+            ("Pneuma", vec!["TOOL"], vec!["TOOL"]),
+            ("Get Lucky (feat. Pharrell Williams)", vec!["Daft Punk", "Nile Rodgers"], vec!["Daft Punk", "Nile Rodgers", "Pharrell Williams"]),
+            ("Sicko Mode", vec!["Travis Scott feat. Drake & Swae Lee"], vec!["Travis Scott", "Drake", "Swae Lee"]),
+            ("Sicko Mode ft. Drake", vec!["Travis Scott feat. DRAKE"], vec!["Travis Scott", "DRAKE"]),
+            ("Teach Your Children", vec!["Crosby, Stills & Nash"], vec!["Crosby, Stills & Nash", "Crosby", "Stills", "Nash"]),
+            ("Pneuma", vec![], vec![]),
+        ];
+
+        for (title, artists, credited) in cases {
+            let metadata = TrackMetadata {
+                title: title.to_string(),
+                artists: artists.iter().map(|artist| artist.to_string()).collect(),
+                ..TrackMetadata::default()
+            };
+            assert_eq!(credited_artists(&metadata), credited, "{}", title);
+        }
+    }
+}
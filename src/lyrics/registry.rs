@@ -0,0 +1,332 @@
+use std::{path::PathBuf, time::Duration};
+
+use futures::future::join_all;
+
+use crate::player::metadata::TrackMetadata;
+
+use super::{embedded::EmbeddedTags, external::{self, ExternalCommand}, genius::{self, Genius}, library::LyricsLibrary, lrclib::{self, LrcLib}, sidecar::SidecarFiles, LyricsError, LyricsFetcher, LyricsResponse, LyricsResult};
+
+/// Lyrics providers in the order they are asked
+#[derive(Default)]
+pub struct ProviderRegistry {
+    providers: Vec<Box<dyn LyricsFetcher>>,
+    strategy: FetchStrategy,
+}
+
+/// How the providers are asked for lyrics
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FetchStrategy {
+    /// One by one in priority order, until one has the song
+    #[default]
+    Fallback,
+    /// All at once, the best result is taken
+    Race,
+}
+
+/// User settings of the providers
+#[derive(Debug, Clone)]
+pub struct RegistryConfig {
+    /// Provider names from the most preferred, the rest come after in their default order
+    pub priority: Vec<String>,
+    pub strategy: FetchStrategy,
+    pub genius_base_url: String,
+    pub lrclib_base_url: String,
+    /// Directories of the lyrics library, it is disabled if there are none
+    pub library_dirs: Vec<PathBuf>,
+    /// Executables that are asked for lyrics like the other online providers
+    pub external_commands: Vec<PathBuf>,
+    pub external_timeout: Duration,
+}
+
+impl Default for RegistryConfig {
+    fn default() -> Self {
+        RegistryConfig {
+            priority: Vec::new(),
+            strategy: FetchStrategy::default(),
+            genius_base_url: genius::DEFAULT_BASE_URL.to_string(),
+            lrclib_base_url: lrclib::DEFAULT_BASE_URL.to_string(),
+            library_dirs: Vec::new(),
+            external_commands: Vec::new(),
+            external_timeout: external::DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl FetchStrategy {
+    pub fn from_setting(setting: &str) -> Self {
+        match setting {
+            "race" => FetchStrategy::Race,
+            _ => FetchStrategy::Fallback,
+        }
+    }
+}
+
+impl ProviderRegistry {
+    /// Results below this are only taken when no provider is more sure
+    const CONFIDENT_MATCH: f32 = 0.8;
+
+    pub fn new(strategy: FetchStrategy) -> Self {
+        ProviderRegistry {
+            providers: Vec::new(),
+            strategy,
+        }
+    }
+
+    /// Every built-in provider, ordered by the configured priority
+    pub fn from_config(config: &RegistryConfig) -> Self {
+        let mut registry = Self::new(config.strategy);
+
+        registry.register(Box::new(SidecarFiles));
+        registry.register(Box::new(EmbeddedTags));
+        if !config.library_dirs.is_empty() {
+            registry.register(Box::new(LyricsLibrary::new(config.library_dirs.clone())));
+        }
+        registry.register(Box::new(Genius::new(&config.genius_base_url)));
+        registry.register(Box::new(LrcLib::new(&config.lrclib_base_url)));
+        for command in &config.external_commands {
+            registry.register(Box::new(ExternalCommand::new(command, config.external_timeout)));
+        }
+
+        registry.sort_by_priority(&config.priority);
+        registry
+    }
+
+    pub fn register(&mut self, provider: Box<dyn LyricsFetcher>) {
+        self.providers.push(provider);
+    }
+
+    /// `priority` has provider names, ignoring case.
+    /// Providers missing from it keep their order after the listed ones.
+    pub fn sort_by_priority(&mut self, priority: &[String]) {
+        self.providers.sort_by_key(|provider| {
+            priority
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(provider.name()))
+                .unwrap_or(priority.len())
+        });
+    }
+
+    pub fn providers(&self) -> impl Iterator<Item = &dyn LyricsFetcher> {
+        self.providers.iter().map(|provider| provider.as_ref())
+    }
+
+    /// Lyrics of the first local provider that has them, errors are only logged
+    pub async fn get_local_lyrics(&self, metadata: &TrackMetadata) -> Option<LyricsResponse> {
+        for provider in self.providers().filter(|provider| provider.is_local()) {
+            if let Ok(response) = Self::ask(provider, metadata).await {
+                return Some(response);
+            }
+        }
+
+        None
+    }
+
+    /// Files of every provider that may have lyrics of `metadata`
+    pub fn watched_files(&self, metadata: &TrackMetadata) -> Vec<PathBuf> {
+        self.providers()
+            .flat_map(|provider| provider.watched_files(metadata))
+            .collect()
+    }
+
+    /// Asks the providers that aren't local, the response names the provider the lyrics are from
+    pub async fn get_lyrics(&self, metadata: &TrackMetadata) -> LyricsResult {
+        match self.strategy {
+            FetchStrategy::Fallback => self.get_lyrics_in_order(metadata).await,
+            FetchStrategy::Race => self.get_best_lyrics(metadata).await,
+        }
+    }
+
+    async fn get_lyrics_in_order(&self, metadata: &TrackMetadata) -> LyricsResult {
+        let mut errors = Vec::new();
+
+        for provider in self.remote_providers() {
+            match Self::ask(provider, metadata).await {
+                Ok(response) => return Ok(response),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        Err(Self::combine_errors(errors))
+    }
+
+    async fn get_best_lyrics(&self, metadata: &TrackMetadata) -> LyricsResult {
+        let results = join_all(self.remote_providers().map(|provider| Self::ask(provider, metadata))).await;
+
+        let mut best: Option<LyricsResponse> = None;
+        let mut errors = Vec::new();
+
+        // Results are in priority order, so a later one has to be strictly better
+        for result in results {
+            match result {
+                Ok(response) => {
+                    let is_better = match &best {
+                        Some(best) => Self::rank(&response) > Self::rank(best),
+                        None => true,
+                    };
+                    if is_better {
+                        best = Some(response);
+                    }
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+
+        best.ok_or_else(|| Self::combine_errors(errors))
+    }
+
+    fn remote_providers(&self) -> impl Iterator<Item = &dyn LyricsFetcher> {
+        self.providers().filter(|provider| !provider.is_local())
+    }
+
+    async fn ask(provider: &dyn LyricsFetcher, metadata: &TrackMetadata) -> LyricsResult {
+        let result = provider.get_lyrics(metadata).await;
+
+        match &result {
+            Ok(_) => println!("registry: {} has {}", provider.name(), metadata.title),
+            Err(error) => println!("registry: {}: {}", provider.name(), error),
+        }
+
+        result.map(|response| LyricsResponse {
+            provider: provider.name().to_string(),
+            ..response
+        })
+    }
+
+    /// Confident matches first, then synced lyrics, then the more confident one
+    fn rank(response: &LyricsResponse) -> (bool, bool, f32) {
+        (
+            response.confidence >= Self::CONFIDENT_MATCH,
+            response.lyrics.is_synced(),
+            response.confidence,
+        )
+    }
+
+    /// Network errors are reported over the rest so that cached lyrics are shown offline
+    fn combine_errors(errors: Vec<LyricsError>) -> LyricsError {
+        let mut errors = errors.into_iter();
+
+        match errors.next() {
+            Some(first) => errors.fold(first, |combined, error| match error {
+                LyricsError::Network(_) => error,
+                _ => combined,
+            }),
+            None => LyricsError::NoProvider,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, future::BoxFuture};
+
+    use crate::{
+        lyrics::{lrc::SyncedLyrics, Lyrics, LyricsError, LyricsFetcher, LyricsResponse, LyricsResult},
+        player::metadata::TrackMetadata,
+    };
+
+    use super::{FetchStrategy, ProviderRegistry};
+
+    enum Outcome {
+        Plain(f32),
+        Synced(f32),
+        NotFound,
+    }
+
+    struct FakeProvider(&'static str, Outcome);
+
+    impl LyricsFetcher for FakeProvider {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn get_lyrics<'a>(&'a self, metadata: &'a TrackMetadata) -> BoxFuture<'a, LyricsResult> {
+            Box::pin(async move {
+                let (lyrics, confidence) = match self.1 {
+                    Outcome::Plain(confidence) => (Lyrics::Plain("Immunity".to_string()), confidence),
+                    Outcome::Synced(confidence) => (Lyrics::Synced("[00:01.00]Immunity".parse::<SyncedLyrics>().unwrap()), confidence),
+                    Outcome::NotFound => return Err(LyricsError::SongNotFound {
+                        song_name: metadata.title.clone(),
+                        artist: String::new(),
+                    }),
+                };
+
+                Ok(LyricsResponse {
+                    artist: String::new(),
+                    track: metadata.title.clone(),
+                    lyrics,
+                    cover_art: Vec::new(),
+                    provider: String::new(),
+                    source_url: None,
+                    confidence,
+                })
+            })
+        }
+    }
+
+    fn registry(strategy: FetchStrategy, providers: Vec<FakeProvider>) -> ProviderRegistry {
+        let mut registry = ProviderRegistry::new(strategy);
+        for provider in providers {
+            registry.register(Box::new(provider));
+        }
+        registry
+    }
+
+    fn provider_of(registry: &ProviderRegistry) -> String {
+        block_on(registry.get_lyrics(&TrackMetadata::default())).unwrap().provider
+    }
+
+    #[test]
+    fn priority_orders_providers() {
+        let mut registry = registry(FetchStrategy::Fallback, vec![
+            FakeProvider("Genius", Outcome::NotFound),
+            FakeProvider("LRCLIB", Outcome::NotFound),
+            FakeProvider("Local files", Outcome::NotFound),
+        ]);
+
+        registry.sort_by_priority(&["local files".to_string(), "genius".to_string()]);
+
+        let names = registry.providers().map(|provider| provider.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Local files", "Genius", "LRCLIB"]);
+    }
+
+    #[test]
+    fn fallback_asks_until_found() {
+        let found = registry(FetchStrategy::Fallback, vec![
+            FakeProvider("Genius", Outcome::NotFound),
+            FakeProvider("LRCLIB", Outcome::Plain(0.5)),
+            FakeProvider("Local files", Outcome::Synced(1.0)),
+        ]);
+        assert_eq!(provider_of(&found), "LRCLIB");
+
+        let not_found = registry(FetchStrategy::Fallback, vec![FakeProvider("Genius", Outcome::NotFound)]);
+        let result = block_on(not_found.get_lyrics(&TrackMetadata::default()));
+        assert!(matches!(result, Err(LyricsError::SongNotFound { .. })));
+
+        let empty = block_on(ProviderRegistry::default().get_lyrics(&TrackMetadata::default()));
+        assert!(matches!(empty, Err(LyricsError::NoProvider)));
+    }
+
+    #[test]
+    fn race_prefers_confident_synced_lyrics() {
+        let synced = registry(FetchStrategy::Race, vec![
+            FakeProvider("Genius", Outcome::Plain(1.0)),
+            FakeProvider("LRCLIB", Outcome::Synced(0.9)),
+            FakeProvider("Local files", Outcome::NotFound),
+        ]);
+        assert_eq!(provider_of(&synced), "LRCLIB");
+
+        // Unsure synced lyrics may be of another song
+        let unsure = registry(FetchStrategy::Race, vec![
+            FakeProvider("Genius", Outcome::Plain(0.9)),
+            FakeProvider("LRCLIB", Outcome::Synced(0.3)),
+        ]);
+        assert_eq!(provider_of(&unsure), "Genius");
+
+        // Ties go to the higher priority
+        let tied = registry(FetchStrategy::Race, vec![
+            FakeProvider("Genius", Outcome::Plain(0.9)),
+            FakeProvider("LRCLIB", Outcome::Plain(0.9)),
+        ]);
+        assert_eq!(provider_of(&tied), "Genius");
+    }
+}
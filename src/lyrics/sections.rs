@@ -0,0 +1,219 @@
+use serde_derive::{Deserialize, Serialize};
+
+use super::normalize;
+
+/// A part of the lyrics under a header like `[Chorus: Rihanna]`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LyricsSection {
+    /// The header without its performers, e.g. `Verse 1`. `None` for the lines before the first header.
+    pub name: Option<String>,
+    pub kind: SectionKind,
+    /// Artists after the colon of the header, empty if the header doesn't tell
+    pub performers: Vec<String>,
+    /// Without blank lines. Empty if the section only repeats an earlier one by name.
+    pub lines: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Intro,
+    Verse,
+    PreChorus,
+    Chorus,
+    PostChorus,
+    Hook,
+    Refrain,
+    Bridge,
+    Interlude,
+    Outro,
+    Other,
+}
+
+impl SectionKind {
+    fn from_name(name: &str) -> Self {
+        let name = normalize::normalize(name);
+        let mut words = name.split(' ');
+
+        match (words.next().unwrap_or_default(), words.next()) {
+            ("pre", Some("chorus")) | ("prechorus", _) => SectionKind::PreChorus,
+            ("post", Some("chorus")) | ("postchorus", _) => SectionKind::PostChorus,
+            ("intro", _) => SectionKind::Intro,
+            ("verse", _) => SectionKind::Verse,
+            ("chorus", _) => SectionKind::Chorus,
+            ("hook", _) => SectionKind::Hook,
+            ("refrain", _) => SectionKind::Refrain,
+            ("bridge", _) => SectionKind::Bridge,
+            ("interlude", _) => SectionKind::Interlude,
+            ("outro", _) => SectionKind::Outro,
+            _ => SectionKind::Other,
+        }
+    }
+
+    /// Sections that are sung again the same way during the song
+    pub fn is_repeated(&self) -> bool {
+        matches!(
+            self,
+            SectionKind::PreChorus | SectionKind::Chorus | SectionKind::PostChorus | SectionKind::Hook | SectionKind::Refrain
+        )
+    }
+}
+
+impl LyricsSection {
+    /// Splits lyrics at their `[Header]` lines.
+    /// Lyrics without headers are a single section without a name.
+    pub fn parse_all(text: &str) -> Vec<LyricsSection> {
+        let mut sections = Vec::new();
+        let mut current = LyricsSection::untitled();
+
+        for line in text.lines().map(str::trim) {
+            if let Some(section) = Self::parse_header(line) {
+                if current.name.is_some() || !current.lines.is_empty() {
+                    sections.push(current);
+                }
+                current = section;
+            } else if !line.is_empty() {
+                current.lines.push(line.to_string());
+            }
+        }
+        if current.name.is_some() || !current.lines.is_empty() {
+            sections.push(current);
+        }
+
+        sections
+    }
+
+    /// `[Chorus: Kendrick Lamar & Rihanna]` is a `Chorus` performed by `Kendrick Lamar` and `Rihanna`
+    fn parse_header(line: &str) -> Option<LyricsSection> {
+        let header = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+        if header.is_empty() || header.contains(['[', ']']) {
+            return None;
+        }
+
+        let (name, performers) = match header.split_once(':') {
+            Some((name, performers)) => (name.trim(), normalize::split_artists(performers)),
+            None => (header, Vec::new()),
+        };
+
+        Some(LyricsSection {
+            name: Some(name.to_string()),
+            kind: SectionKind::from_name(name),
+            performers,
+            lines: Vec::new(),
+        })
+    }
+
+    fn untitled() -> Self {
+        LyricsSection {
+            name: None,
+            kind: SectionKind::Other,
+            performers: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// The header as it would be written, without the brackets
+    pub fn header(&self) -> Option<String> {
+        let name = self.name.as_ref()?;
+
+        if self.performers.is_empty() {
+            Some(name.clone())
+        } else {
+            Some(format!("{}: {}", name, self.performers.join(" & ")))
+        }
+    }
+
+    pub fn to_plain(&self) -> String {
+        self.header()
+            .map(|header| format!("[{}]", header))
+            .into_iter()
+            .chain(self.lines.iter().cloned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// For each section, the earlier section it repeats.
+    /// Choruses and the like repeat an earlier one if they have the same lines,
+    /// or the same name and no lines of their own.
+    pub fn repeats(sections: &[LyricsSection]) -> Vec<Option<usize>> {
+        sections
+            .iter()
+            .enumerate()
+            .map(|(index, section)| {
+                if !section.kind.is_repeated() {
+                    return None;
+                }
+
+                sections[..index].iter().position(|earlier| {
+                    earlier.kind == section.kind
+                        && !earlier.lines.is_empty()
+                        && if section.lines.is_empty() {
+                            earlier.name == section.name
+                        } else {
+                            earlier.lines == section.lines
+                        }
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LyricsSection, SectionKind};
+
+    #[test]
+    fn sections_are_parsed() {
+        let sections = LyricsSection::parse_all("Spoken intro
+
+[Verse 1: Kendrick Lamar]
+Nobody pray for me
+It been that day for me
+
+[Pre-Chorus]
+Ayy, I remember syrup sandwiches
+
+[Chorus: Kendrick Lamar & Rihanna]
+Sit down, be humble
+[Bridge]");
+
+        let summary = sections
+            .iter()
+            .map(|section| (section.name.as_deref(), section.kind, section.performers.len(), section.lines.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![
+            (None, SectionKind::Other, 0, 1),
+            (Some("Verse 1"), SectionKind::Verse, 1, 2),
+            (Some("Pre-Chorus"), SectionKind::PreChorus, 0, 1),
+            (Some("Chorus"), SectionKind::Chorus, 2, 1),
+            (Some("Bridge"), SectionKind::Bridge, 0, 0),
+        ]);
+        assert_eq!(sections[3].performers, vec!["Kendrick Lamar", "Rihanna"]);
+        assert_eq!(sections[3].to_plain(), "[Chorus: Kendrick Lamar & Rihanna]\nSit down, be humble");
+
+        // Without headers
+        let plain = LyricsSection::parse_all("We are born of one breath\n\nOne word");
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].name, None);
+        assert_eq!(plain[0].lines, vec!["We are born of one breath", "One word"]);
+
+        // Only whole lines are headers
+        assert_eq!(LyricsSection::parse_all("[Intro] Hey\n[[Odd]]")[0].lines.len(), 2);
+    }
+
+    #[test]
+    fn repeated_choruses_are_found() {
+        let sections = LyricsSection::parse_all("[Chorus]
+Exhale, expel
+[Verse 1]
+Enumerate all that I'm to do
+[Chorus]
+Exhale, expel
+[Verse 2]
+Enumerate all that I'm to do
+[Chorus]
+[Chorus]
+Recast my tale");
+
+        assert_eq!(LyricsSection::repeats(&sections), vec![None, None, Some(0), None, Some(0), None]);
+    }
+}
@@ -0,0 +1,114 @@
+use std::{fs, io, path::PathBuf};
+
+use futures::future::BoxFuture;
+
+use crate::player::metadata::TrackMetadata;
+
+use super::{Lyrics, LyricsError, LyricsFetcher, LyricsResponse, LyricsResult};
+
+static PROVIDER_NAME: &'static str = "Sidecar files";
+/// In the order they are looked for
+const SIDECAR_EXTENSIONS: [&'static str; 4] = ["lrc", "LRC", "txt", "TXT"];
+
+/// Lyrics saved next to local audio files, `Song.flac` has them in `Song.lrc` or `Song.txt`
+pub struct SidecarFiles;
+
+impl SidecarFiles {
+    fn candidates(metadata: &TrackMetadata) -> Vec<PathBuf> {
+        match metadata.local_path() {
+            Some(audio_path) => SIDECAR_EXTENSIONS
+                .iter()
+                .map(|extension| audio_path.with_extension(extension))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn read_lyrics(metadata: &TrackMetadata) -> LyricsResult {
+        for path in Self::candidates(metadata) {
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => {
+                    println!("sidecar: Couldn't read {:?}: {}", path, error);
+                    continue;
+                }
+            };
+
+            // .lrc files are sometimes only plain text
+            let lyrics = match Lyrics::from_text(&text) {
+                Some(lyrics) => lyrics,
+                None => continue,
+            };
+
+            return Ok(LyricsResponse {
+                artist: metadata.primary_artist().unwrap_or_default().to_string(),
+                track: metadata.title.clone(),
+                lyrics,
+                cover_art: Vec::new(),
+                provider: PROVIDER_NAME.to_string(),
+                source_url: Some(format!("file://{}", path.display())),
+                confidence: 1.0,
+            });
+        }
+
+        Err(LyricsError::SongNotFound {
+            song_name: metadata.title.clone(),
+            artist: metadata.primary_artist().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+impl LyricsFetcher for SidecarFiles {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn watched_files(&self, metadata: &TrackMetadata) -> Vec<PathBuf> {
+        Self::candidates(metadata)
+    }
+
+    fn get_lyrics<'a>(&'a self, metadata: &'a TrackMetadata) -> BoxFuture<'a, LyricsResult> {
+        Box::pin(async move { Self::read_lyrics(metadata) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{lyrics::{Lyrics, LyricsError}, player::metadata::TrackMetadata};
+
+    use super::SidecarFiles;
+
+    #[test]
+    fn sidecar_lyrics_are_found() {
+        let dir = std::env::temp_dir().join(format!("sync-lyrics-sidecar-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let metadata = TrackMetadata {
+            title: "Culling Voices".to_string(),
+            url: Some(format!("file://{}/07%20-%20Culling%20Voices.flac", dir.display())),
+            ..TrackMetadata::default()
+        };
+
+        assert!(matches!(SidecarFiles::read_lyrics(&metadata), Err(LyricsError::SongNotFound { .. })));
+
+        fs::write(dir.join("07 - Culling Voices.txt"), "Don't you wanna be my friend?\n").unwrap();
+        let plain = SidecarFiles::read_lyrics(&metadata).unwrap();
+        assert_eq!(plain.lyrics.to_plain(), "Don't you wanna be my friend?");
+
+        // .lrc is preferred
+        fs::write(dir.join("07 - Culling Voices.lrc"), "[00:31.50]Don't you wanna be my friend?\n").unwrap();
+        let synced = SidecarFiles::read_lyrics(&metadata).unwrap();
+        assert!(matches!(synced.lyrics, Lyrics::Synced(_)));
+        assert!(synced.source_url.unwrap().ends_with("07 - Culling Voices.lrc"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,38 +1,72 @@
-use std::{thread::{spawn, JoinHandle}, rc::Rc};
+use std::{thread::{spawn, JoinHandle}, rc::Rc, time::Duration};
 
 use application::LyricsApplication;
 use gdk::{prelude::{ApplicationExt, ApplicationExtManual}, gio::ApplicationFlags};
-use lyrics::genius::Genius;
-use spotify::{spotify_event_producer::SpotifyEventProducer, spotify_event::SpotifyEvent};
-use tokio::runtime::Runtime;
-use crossbeam_channel::Receiver;
-use ui::UIEvent;
+use lyrics::{cache::{CacheError, CacheLimits, CachedLyrics, LyricsCache}, file_watcher::FileWatcher, registry::{ProviderRegistry, RegistryConfig}, LyricsError, LyricsOrigin};
+use gio::prelude::{NetworkMonitorExt, SettingsExt};
+use player::{
+    player_event_producer::PlayerEventProducer,
+    metadata::TrackMetadata,
+    player_event::{PlayerEvent, PlayerEventKind, player_name},
+    player_selector::{PlayerSelection, PlayerSelector},
+};
+use tokio::{runtime::Runtime, sync::mpsc::UnboundedReceiver, time::MissedTickBehavior};
+use crossbeam_channel::{Receiver, select};
+use ui::{CacheCommand, UIEvent};
 
 mod app_state;
 mod application;
+mod cli;
 mod configs;
-mod spotify;
+mod player;
 mod lyrics;
 mod widgets;
 mod ui;
 
+/// How often local lyrics files are checked for edits
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 #[tokio::main]
 async fn main() {
+    let cache_limits = configs::cache_limits();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some(exit_code) = cli::run_cache_command(&args, cache_limits) {
+        std::process::exit(exit_code);
+    }
+
     let application = adw::Application::new(
         Some("com.github.onsah.sync-lyrics"),
         ApplicationFlags::empty(),
     );
 
-    let (spotify_event_sender, spotify_event_receiver) = crossbeam_channel::unbounded::<SpotifyEvent>();
+    let (player_event_sender, player_event_receiver) = crossbeam_channel::unbounded::<PlayerEvent>();
+
+    // The fetcher waits for player events while it fetches
+    let (fl_player_event_sender, fl_player_event_receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    let (player_selection_sender, player_selection_receiver) = crossbeam_channel::unbounded();
+
+    let (cache_command_sender, cache_command_receiver) = crossbeam_channel::unbounded();
 
-    let (fl_spotify_event_sender, fl_spotify_event_receiver) = crossbeam_channel::unbounded();
+    let player_selection = PlayerSelection::from_setting(
+        &configs::settings()
+            .map(|settings| settings.string(configs::SELECTED_PLAYER_KEY).to_string())
+            .unwrap_or_default()
+    );
 
     // TODO: Close thread on app close
-    SpotifyEventProducer::init(spotify_event_sender);
+    PlayerEventProducer::init(player_event_sender);
 
     let (ui_event_sender, ui_event_receiver) = crossbeam_channel::unbounded();
 
-    fetch_lyrics(fl_spotify_event_receiver, ui_event_sender.clone());
+    fetch_lyrics(
+        fl_player_event_receiver,
+        cache_command_receiver,
+        ui_event_sender.clone(),
+        cache_limits,
+        configs::registry_config(),
+    );
 
     // Have to do this stupid thing, 
     // Because connect_activate is Fn and not FnOnce
@@ -40,47 +74,288 @@ async fn main() {
     // called multiple times.
     let ui_event_receiver = Rc::from(ui_event_receiver);
 
+    let initial_player_selection = player_selection.clone();
+
     spawn(move || {
+        let mut player_selector = PlayerSelector::new(initial_player_selection);
+        let mut player_names = Vec::new();
+
         loop {
-            let event = spotify_event_receiver.recv().unwrap();
-            fl_spotify_event_sender.send(event.clone()).unwrap();
-            let SpotifyEvent::SongChanged { song_name, artist_name } = event;
-            ui_event_sender.send(
-                UIEvent::FetchingLyrics {
-                    song_name, artist_name,
+            // Only the events of the followed player go further
+            let events = select! {
+                recv(player_event_receiver) -> event => player_selector.handle(event.unwrap()),
+                recv(player_selection_receiver) -> selection => player_selector.select(selection.unwrap()),
+            };
+
+            let new_player_names = player_selector.player_names();
+            if new_player_names != player_names {
+                player_names = new_player_names;
+                ui_event_sender.send(UIEvent::PlayersChanged(player_names.clone())).unwrap();
+            }
+
+            for event in events {
+                let player_name = player_name(&event.player).to_string();
+                // The UI has to wait for the lyrics before the fetcher can answer
+                match event.kind.clone() {
+                    PlayerEventKind::Connected => {
+                        ui_event_sender.send(UIEvent::PlayerConnected { player_name }).unwrap();
+                    }
+                    PlayerEventKind::Disconnected => {
+                        ui_event_sender.send(UIEvent::PlayerDisconnected).unwrap();
+                    }
+                    PlayerEventKind::SongChanged(metadata) => {
+                        ui_event_sender.send(
+                            UIEvent::FetchingLyrics {
+                                metadata,
+                            }
+                        ).unwrap();
+                    }
+                    PlayerEventKind::PositionChanged(position_clock) => {
+                        ui_event_sender.send(UIEvent::PositionChanged(position_clock)).unwrap();
+                    }
                 }
-            ).unwrap();
+                fl_player_event_sender.send(event).unwrap();
+            }
         }
     });
 
     application.connect_activate(move |app| {
-        LyricsApplication::init(app, ui_event_receiver.clone());
+        LyricsApplication::init(
+            app,
+            ui_event_receiver.clone(),
+            player_selection_sender.clone(),
+            player_selection.clone(),
+            cache_command_sender.clone(),
+        );
     });
 
     application.run();
 }
 
-fn fetch_lyrics(spotify_event_receiver: Receiver<SpotifyEvent>, ui_event_sender: crossbeam_channel::Sender<UIEvent>) -> JoinHandle<()> {
+fn fetch_lyrics(
+    mut player_event_receiver: UnboundedReceiver<PlayerEvent>,
+    cache_command_receiver: Receiver<CacheCommand>,
+    ui_event_sender: crossbeam_channel::Sender<UIEvent>,
+    cache_limits: CacheLimits,
+    registry_config: RegistryConfig,
+) -> JoinHandle<()> {
     spawn(move || {
-        let mut lyrics_fetcher = Genius::new();
+        let providers = ProviderRegistry::from_config(&registry_config);
         let rt = Runtime::new().unwrap();
-        loop {
-            let spotify_event = spotify_event_receiver.recv().unwrap();
-            
-            match spotify_event {
-                SpotifyEvent::SongChanged { song_name, artist_name } => {
-                    let lyrics_result = rt.block_on(
-                        lyrics_fetcher.get_lyrics(&song_name, &artist_name)
-                    ).unwrap();
-
-                    ui_event_sender.send(UIEvent::SongInformationFetched { 
-                        song_name, 
-                        artist_name, 
+        let cache = LyricsCache::open(cache_limits)
+            .map_err(|error| println!("fetch_lyrics: Lyrics won't be cached: {}", error))
+            .ok();
+        let mut cache_command_receiver = forward_to_async(cache_command_receiver);
+        // Song of the shown lyrics
+        let mut current_song = None;
+        // Song that arrived while the previous one was being fetched
+        let mut next_song = None;
+        // Local lyrics files of the current song
+        let mut file_watcher = FileWatcher::default();
+
+        rt.block_on(async move {
+            // On its own timer, so that frequent player events can't hold off the check for edits
+            let mut file_poll = tokio::time::interval(FILE_POLL_INTERVAL);
+            file_poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                let metadata = match next_song.take() {
+                    Some(metadata) => metadata,
+                    None => tokio::select! {
+                        player_event = player_event_receiver.recv() => match player_event {
+                            Some(PlayerEvent { kind: PlayerEventKind::SongChanged(metadata), .. }) => metadata,
+                            Some(PlayerEvent { kind: PlayerEventKind::Disconnected, .. }) => {
+                                current_song = None;
+                                file_watcher = FileWatcher::default();
+                                continue;
+                            }
+                            Some(_) => continue,
+                            None => {
+                                println!("fetch_lyrics: Player events ended");
+                                return;
+                            }
+                        },
+                        Some(command) = cache_command_receiver.recv() => {
+                            match &cache {
+                                Some(cache) => match run_cache_command(cache, command, current_song.as_ref(), &ui_event_sender) {
+                                    Ok(song_to_refetch) => next_song = song_to_refetch,
+                                    Err(error) => println!("fetch_lyrics: Cache command failed: {}", error),
+                                },
+                                None => println!("fetch_lyrics: There is no cache"),
+                            }
+                            continue;
+                        }
+                        _ = file_poll.tick() => {
+                            // Local lyrics were edited
+                            match &current_song {
+                                Some(metadata) if file_watcher.poll() => {
+                                    ui_event_sender.send(UIEvent::FetchingLyrics { metadata: metadata.clone() }).unwrap();
+                                    next_song = Some(metadata.clone());
+                                }
+                                _ => (),
+                            }
+                            continue;
+                        }
+                    },
+                };
+                current_song = Some(metadata.clone());
+                file_watcher = FileWatcher::new(providers.watched_files(&metadata));
+
+                let local_lyrics = providers.get_local_lyrics(&metadata).await;
+
+                let lyrics_result = match local_lyrics {
+                    Some(local_lyrics) => Ok((local_lyrics, LyricsOrigin::Local)),
+                    None => match cached_lyrics(cache.as_ref(), &metadata) {
+                        Some(cached) if !cached.is_stale => Ok((cached.response, cache_origin())),
+                        stale => {
+                            let fetched = tokio::select! {
+                                lyrics_result = providers.get_lyrics(&metadata) => lyrics_result,
+                                interruption = wait_for_interruption(&mut player_event_receiver, &metadata) => {
+                                    match interruption {
+                                        Interruption::SongChanged(newer_song) => {
+                                            println!("fetch_lyrics: Abandoned {}, song changed", metadata.title);
+                                            next_song = Some(newer_song);
+                                        }
+                                        Interruption::Disconnected => {
+                                            println!("fetch_lyrics: Abandoned {}, player disconnected", metadata.title);
+                                            current_song = None;
+                                            file_watcher = FileWatcher::default();
+                                        }
+                                        Interruption::Closed => {
+                                            println!("fetch_lyrics: Player events ended");
+                                            return;
+                                        }
+                                    }
+                                    continue;
+                                }
+                            };
+
+                            match (fetched, stale) {
+                                (Ok(lyrics_result), _) => {
+                                    if let Some(Err(error)) = cache.as_ref().map(|cache| cache.insert(&metadata, &lyrics_result)) {
+                                        println!("fetch_lyrics: Couldn't cache {}: {}", metadata.title, error);
+                                    }
+                                    Ok((lyrics_result, LyricsOrigin::Network))
+                                }
+                                // Outdated lyrics are better than none
+                                (Err(error), Some(stale)) => {
+                                    println!("fetch_lyrics: Showing the cached {}: {}", metadata.title, error);
+                                    let origin = match error {
+                                        LyricsError::Network(_) => LyricsOrigin::Offline,
+                                        _ => LyricsOrigin::Cache,
+                                    };
+                                    Ok((stale.response, origin))
+                                }
+                                (Err(error), None) => Err(error),
+                            }
+                        }
+                    }
+                };
+
+                let ui_event = match lyrics_result {
+                    Ok((lyrics_result, origin)) => UIEvent::SongInformationFetched { 
+                        metadata, 
                         lyrics: lyrics_result.lyrics, 
                         cover_art: lyrics_result.cover_art, 
-                    }).unwrap();
-                }
+                        provider: lyrics_result.provider,
+                        origin,
+                    },
+                    Err(error) => {
+                        println!("fetch_lyrics: {}", error);
+                        match error {
+                            LyricsError::Network(_) => UIEvent::NetworkFailed { metadata },
+                            _ => UIEvent::SongNotFound { metadata },
+                        }
+                    }
+                };
+
+                ui_event_sender.send(ui_event).unwrap();
             }
-        }
+        });
     })
 }
+
+/// Forwards the messages of `receiver` from a thread, so that futures can wait for them
+fn forward_to_async<T: Send + 'static>(receiver: Receiver<T>) -> UnboundedReceiver<T> {
+    let (sender, async_receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    spawn(move || {
+        for message in receiver {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    async_receiver
+}
+
+fn cached_lyrics(cache: Option<&LyricsCache>, metadata: &TrackMetadata) -> Option<CachedLyrics> {
+    match cache?.get(metadata) {
+        Ok(cached) => cached,
+        Err(error) => {
+            println!("fetch_lyrics: Couldn't read cached {}: {}", metadata.title, error);
+            None
+        }
+    }
+}
+
+/// Fresh cached lyrics are used without touching the network, they are marked offline if it is down anyway
+fn cache_origin() -> LyricsOrigin {
+    if gio::NetworkMonitor::default().is_network_available() {
+        LyricsOrigin::Cache
+    } else {
+        LyricsOrigin::Offline
+    }
+}
+
+/// Returns the song to fetch again, if any
+fn run_cache_command(
+    cache: &LyricsCache,
+    command: CacheCommand,
+    current_song: Option<&TrackMetadata>,
+    ui_event_sender: &crossbeam_channel::Sender<UIEvent>,
+) -> Result<Option<TrackMetadata>, CacheError> {
+    match command {
+        CacheCommand::Clear => {
+            cache.clear()?;
+            Ok(None)
+        }
+        CacheCommand::RefetchCurrentSong => match current_song {
+            Some(metadata) => {
+                cache.purge(metadata.primary_artist().unwrap_or_default(), &metadata.title)?;
+                // The UI only takes the lyrics of the song it is waiting for
+                ui_event_sender.send(UIEvent::FetchingLyrics { metadata: metadata.clone() }).unwrap();
+                Ok(Some(metadata.clone()))
+            }
+            None => Ok(None),
+        },
+        CacheCommand::ShowStats => {
+            ui_event_sender.send(UIEvent::CacheStats(cache.stats()?)).unwrap();
+            Ok(None)
+        }
+    }
+}
+
+/// Why the fetch of a song is dropped
+enum Interruption {
+    /// A different song started
+    SongChanged(TrackMetadata),
+    /// The player closed
+    Disconnected,
+    /// No more player events will come
+    Closed,
+}
+
+/// Completes when a different song than `metadata` starts or the player closes, so the fetch for it can be dropped
+async fn wait_for_interruption(player_event_receiver: &mut UnboundedReceiver<PlayerEvent>, metadata: &TrackMetadata) -> Interruption {
+    loop {
+        match player_event_receiver.recv().await {
+            Some(PlayerEvent { kind: PlayerEventKind::SongChanged(newer_metadata), .. })
+                if !newer_metadata.is_same_track(metadata) => return Interruption::SongChanged(newer_metadata),
+            Some(PlayerEvent { kind: PlayerEventKind::Disconnected, .. }) => return Interruption::Disconnected,
+            // Positions only matter to the UI and the song is the same
+            Some(_) => (),
+            None => return Interruption::Closed,
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::{collections::HashMap, ffi::OsString, os::unix::ffi::OsStringExt, path::PathBuf, time::Duration};
+
+use dbus::arg::{ArgType, RefArg};
+use thiserror::Error;
+
+/// Track information from the `Metadata` property of `org.mpris.MediaPlayer2.Player`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TrackMetadata {
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub album_artists: Vec<String>,
+    pub length: Option<Duration>,
+    pub track_id: Option<String>,
+    pub art_url: Option<String>,
+    /// `xesam:url`, e.g. `file:///home/user/Music/song.flac` for local files
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MetadataError {
+    #[error("Metadata is not a dictionary")]
+    NotADictionary,
+    #[error("Metadata has no title")]
+    MissingTitle,
+}
+
+impl TrackMetadata {
+    /// Decodes the `a{sv}` metadata, possibly wrapped in variants.
+    /// Entries with unexpected types are ignored, only a missing title is an error.
+    pub fn decode(metadata: &dyn RefArg) -> Result<Self, MetadataError> {
+        let entries = Self::entries(metadata).ok_or(MetadataError::NotADictionary)?;
+
+        let string = |key: &str| {
+            entries
+                .get(key)
+                .and_then(|value| value.as_str())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let strings = |key: &str| {
+            entries
+                .get(key)
+                .map(|value| Self::strings(*value))
+                .unwrap_or_default()
+        };
+
+        let title = string("xesam:title").ok_or(MetadataError::MissingTitle)?;
+
+        Ok(TrackMetadata {
+            title,
+            artists: strings("xesam:artist"),
+            album: string("xesam:album"),
+            album_artists: strings("xesam:albumArtist"),
+            length: entries.get("mpris:length").and_then(|value| Self::length(*value)),
+            track_id: string("mpris:trackid"),
+            art_url: string("mpris:artUrl"),
+            url: string("xesam:url"),
+        })
+    }
+
+    pub fn primary_artist(&self) -> Option<&str> {
+        self.artists.first().map(|artist| artist.as_str())
+    }
+
+    /// Path of the audio file if the player plays a local file
+    pub fn local_path(&self) -> Option<PathBuf> {
+        let url = self.url.as_deref()?;
+        let path = url
+            .strip_prefix("file://")
+            .map(|path| path.strip_prefix("localhost").unwrap_or(path))
+            .filter(|path| path.starts_with('/'))?;
+
+        Some(PathBuf::from(OsString::from_vec(Self::percent_decode(path)?)))
+    }
+
+    /// Some players use the same track id for everything, so the rest is compared as well
+    pub fn is_same_track(&self, other: &TrackMetadata) -> bool {
+        self.track_id == other.track_id
+            && self.title == other.title
+            && self.artists == other.artists
+            && self.album == other.album
+    }
+
+    fn entries(metadata: &dyn RefArg) -> Option<HashMap<&str, &dyn RefArg>> {
+        let mut value = metadata;
+        while value.arg_type() == ArgType::Variant {
+            value = value.as_iter()?.next()?;
+        }
+
+        if value.arg_type() != ArgType::Array {
+            return None;
+        }
+
+        // Dictionaries are iterated as key, value, key, value...
+        let mut iter = value.as_iter()?;
+        let mut entries = HashMap::new();
+
+        while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+            if let Some(key) = key.as_str() {
+                entries.insert(key, value);
+            }
+        }
+
+        Some(entries)
+    }
+
+    /// Players send either a list of strings or a single string
+    fn strings(value: &dyn RefArg) -> Vec<String> {
+        match (value.as_str(), value.as_iter()) {
+            (Some(string), _) if !string.trim().is_empty() => vec![string.trim().to_string()],
+            (None, Some(iter)) => iter.flat_map(Self::strings).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn percent_decode(text: &str) -> Option<Vec<u8>> {
+        let mut bytes = text.bytes();
+        let mut decoded = Vec::with_capacity(text.len());
+
+        while let Some(byte) = bytes.next() {
+            if byte == b'%' {
+                let hex = [bytes.next()?, bytes.next()?];
+                decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            } else {
+                decoded.push(byte);
+            }
+        }
+
+        Some(decoded)
+    }
+
+    /// In microseconds, the spec says `x` but some players send `t` or `d`
+    fn length(value: &dyn RefArg) -> Option<Duration> {
+        let micros = value
+            .as_i64()
+            .or_else(|| value.as_u64().map(|micros| micros as i64))
+            .or_else(|| value.as_f64().map(|micros| micros as i64))
+            .filter(|micros| *micros > 0)?;
+
+        Some(Duration::from_micros(micros as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+    use dbus::{arg::{RefArg, Variant}, Message, Path};
+
+    use super::{MetadataError, TrackMetadata};
+
+    type PropMap = HashMap<String, Variant<Box<dyn RefArg>>>;
+
+    fn entry(map: &mut PropMap, key: &str, value: impl RefArg + 'static) {
+        map.insert(key.to_string(), Variant(Box::new(value)));
+    }
+
+    /// Sends `metadata` through a real `PropertiesChanged` message and decodes it on the other side
+    fn decode(metadata: PropMap) -> Result<TrackMetadata, MetadataError> {
+        let mut changed = PropMap::new();
+        entry(&mut changed, "Metadata", metadata);
+
+        let message = Message::new_signal(
+            "/org/mpris/MediaPlayer2",
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+        )
+        .unwrap()
+        .append3("org.mpris.MediaPlayer2.Player", changed, Vec::<String>::new());
+
+        let (_, mut changed): (String, PropMap) = message.read2().unwrap();
+        let metadata = changed.remove("Metadata").unwrap();
+
+        TrackMetadata::decode(&metadata)
+    }
+
+    #[test]
+    fn spotify_metadata_works() {
+        let mut metadata = PropMap::new();
+        entry(&mut metadata, "mpris:trackid", Path::from("/com/spotify/track/03sEzk1VyrUZSgyhoQR0LZ"));
+        entry(&mut metadata, "mpris:length", 621_000_000u64);
+        entry(&mut metadata, "mpris:artUrl", "https://i.scdn.co/image/ab67616d0000b273".to_string());
+        entry(&mut metadata, "xesam:album", "Fear Inoculum".to_string());
+        entry(&mut metadata, "xesam:albumArtist", vec!["TOOL".to_string()]);
+        entry(&mut metadata, "xesam:artist", vec!["TOOL".to_string(), "Danny Carey".to_string()]);
+        entry(&mut metadata, "xesam:title", "Pneuma".to_string());
+        entry(&mut metadata, "xesam:url", "https://open.spotify.com/track/03sEzk1VyrUZSgyhoQR0LZ".to_string());
+        entry(&mut metadata, "xesam:autoRating", 0.6f64);
+
+        let decoded = decode(metadata).unwrap();
+
+        assert_eq!(decoded, TrackMetadata {
+            title: "Pneuma".to_string(),
+            artists: vec!["TOOL".to_string(), "Danny Carey".to_string()],
+            album: Some("Fear Inoculum".to_string()),
+            album_artists: vec!["TOOL".to_string()],
+            length: Some(Duration::from_secs(621)),
+            track_id: Some("/com/spotify/track/03sEzk1VyrUZSgyhoQR0LZ".to_string()),
+            art_url: Some("https://i.scdn.co/image/ab67616d0000b273".to_string()),
+            url: Some("https://open.spotify.com/track/03sEzk1VyrUZSgyhoQR0LZ".to_string()),
+        });
+        assert_eq!(decoded.primary_artist(), Some("TOOL"));
+    }
+
+    #[test]
+    fn firefox_metadata_works() {
+        // Firefox sends empty albums and a single string as the artist for some sites
+        let mut metadata = PropMap::new();
+        entry(&mut metadata, "mpris:trackid", Path::from("/org/mpris/MediaPlayer2/firefox"));
+        entry(&mut metadata, "xesam:title", "Tool - Invincible (Audio)".to_string());
+        entry(&mut metadata, "xesam:artist", "TOOL - Topic".to_string());
+        entry(&mut metadata, "xesam:album", "".to_string());
+
+        let decoded = decode(metadata).unwrap();
+
+        assert_eq!(decoded.title, "Tool - Invincible (Audio)");
+        assert_eq!(decoded.artists, vec!["TOOL - Topic".to_string()]);
+        assert_eq!(decoded.album, None);
+        assert_eq!(decoded.length, None);
+        assert_eq!(decoded.local_path(), None);
+    }
+
+    #[test]
+    fn mpv_metadata_works() {
+        // mpv sends the length as `x` and has no artist for untagged files
+        let mut metadata = PropMap::new();
+        entry(&mut metadata, "mpris:length", 252_000_000i64);
+        entry(&mut metadata, "xesam:title", "07 - Culling Voices.flac".to_string());
+        entry(&mut metadata, "xesam:url", "file:///home/user/Music/07%20-%20Culling%20Voices.flac".to_string());
+
+        let decoded = decode(metadata).unwrap();
+
+        assert_eq!(decoded.artists, Vec::<String>::new());
+        assert_eq!(decoded.primary_artist(), None);
+        assert_eq!(decoded.length, Some(Duration::from_secs(252)));
+        assert_eq!(decoded.url.as_deref(), Some("file:///home/user/Music/07%20-%20Culling%20Voices.flac"));
+        assert_eq!(decoded.local_path(), Some(PathBuf::from("/home/user/Music/07 - Culling Voices.flac")));
+    }
+
+    #[test]
+    fn missing_title_is_an_error() {
+        let mut metadata = PropMap::new();
+        entry(&mut metadata, "xesam:artist", vec!["TOOL".to_string()]);
+        entry(&mut metadata, "xesam:title", 42i32);
+
+        assert_eq!(decode(metadata), Err(MetadataError::MissingTitle));
+        assert_eq!(TrackMetadata::decode(&"Pneuma".to_string()), Err(MetadataError::NotADictionary));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod metadata;
+pub mod player_event;
+pub mod player_event_producer;
+pub mod player_selector;
+pub mod position_clock;
@@ -0,0 +1,30 @@
+use super::{metadata::TrackMetadata, position_clock::PositionClock};
+
+pub const MPRIS_BUS_NAME_PREFIX: &'static str = "org.mpris.MediaPlayer2.";
+
+#[derive(Debug, Clone)]
+pub struct PlayerEvent {
+    /// Bus name of the player, e.g. `org.mpris.MediaPlayer2.spotify`
+    pub player: String,
+    pub kind: PlayerEventKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum PlayerEventKind {
+    /// The player appeared on the bus, either at startup or when it is launched
+    Connected,
+    Disconnected,
+    SongChanged(TrackMetadata),
+    PositionChanged(PositionClock),
+}
+
+/// Name of the player that stays the same across launches,
+/// `org.mpris.MediaPlayer2.firefox.instance_1_84` becomes `firefox`
+pub fn player_name(bus_name: &str) -> &str {
+    let name = bus_name.strip_prefix(MPRIS_BUS_NAME_PREFIX).unwrap_or(bus_name);
+
+    match name.find(".instance") {
+        Some(index) => &name[..index],
+        None => name,
+    }
+}
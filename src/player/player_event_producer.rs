@@ -0,0 +1,332 @@
+use std::{time::Duration, collections::HashMap, thread::spawn, sync::{Arc, Mutex}};
+use crossbeam_channel::Sender;
+
+use dbus::{blocking::{Connection, Proxy, stdintf::org_freedesktop_dbus::Properties}, arg::{self, RefArg}, Message, message::SignalArgs};
+
+use super::{
+    metadata::{MetadataError, TrackMetadata},
+    player_event::{PlayerEvent, PlayerEventKind, MPRIS_BUS_NAME_PREFIX},
+    position_clock::{PlaybackStatus, PositionClock},
+};
+
+const DBUS_NAME: &'static str = "org.freedesktop.DBus";
+const DBUS_PATH: &'static str = "/org/freedesktop/DBus";
+const MPRIS_PATH: &'static str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &'static str = "org.mpris.MediaPlayer2.Player";
+
+/// Unique connection names (e.g. `:1.42`) of the players to their bus names.
+/// Signals are sent from the unique name, so they are mapped back with this.
+type PlayerOwners = Arc<Mutex<HashMap<String, String>>>;
+
+pub struct PlayerEventProducer;
+
+impl PlayerEventProducer {
+    pub fn init(sender: Sender<PlayerEvent>) {
+        let connection = Connection::new_session().expect("Couldn't create connection");
+        spawn(move || {
+            Self::init_player_listener(sender, &connection);
+
+            loop {
+                connection.process(Duration::from_millis(50)).unwrap();
+            }
+        });
+    }
+
+    fn init_player_listener(
+        sender: Sender<PlayerEvent>,
+        connection: &Connection
+    ) {
+        let owners: PlayerOwners = Arc::default();
+
+        for player in Self::list_players(connection) {
+            Self::attach_player(&sender, connection, &owners, player);
+        }
+
+        // Players that are launched or closed later
+        {
+            let sender = sender.clone();
+            let owners = owners.clone();
+            let rule = NameOwnerChanged::match_rule(None, None)
+                .with_sender(DBUS_NAME)
+                .static_clone();
+
+            let _id = connection.add_match(
+                rule,
+                move |changed: NameOwnerChanged, connection: &Connection, _: &Message| {
+                    if !changed.name.starts_with(MPRIS_BUS_NAME_PREFIX) {
+                        return true;
+                    }
+
+                    if !changed.old_owner.is_empty() {
+                        println!("player_event_producer: {} disconnected", changed.name);
+                        owners.lock().unwrap().remove(&changed.old_owner);
+                        Self::send(&sender, &changed.name, PlayerEventKind::Disconnected);
+                    }
+                    if !changed.new_owner.is_empty() {
+                        println!("player_event_producer: {} connected", changed.name);
+                        Self::attach_player(&sender, connection, &owners, changed.name);
+                    }
+
+                    true
+                },
+            );
+        }
+
+        {
+            let sender = sender.clone();
+            let owners = owners.clone();
+            let rule = PropertiesChanged::match_rule(None, None)
+                .with_path(MPRIS_PATH)
+                .static_clone();
+
+            let _id = connection.add_match(
+                rule,
+                move |p: PropertiesChanged, connection: &Connection, message: &Message| {
+                    let player = match Self::player_of(&owners, message) {
+                        Some(player) => player,
+                        None => return true,
+                    };
+
+                    match p.metadata {
+                        Some(metadata) => Self::send_song(&sender, &player, metadata),
+                        None => println!("player_event_producer: {} No song changed", player),
+                    }
+
+                    // Position isn't signaled so it is read whenever playback might have jumped
+                    if p.playback_changed {
+                        Self::send_position(&sender, &Self::player_proxy(connection, &player), &player, None);
+                    }
+
+                    true
+                },
+            );
+        }
+
+        {
+            let rule = Seeked::match_rule(None, None)
+                .with_path(MPRIS_PATH)
+                .static_clone();
+
+            let _id = connection.add_match(
+                rule,
+                move |seeked: Seeked, connection: &Connection, message: &Message| {
+                    if let Some(player) = Self::player_of(&owners, message) {
+                        let position = Duration::from_micros(seeked.position.max(0) as u64);
+                        let proxy = Self::player_proxy(connection, &player);
+                        Self::send_position(&sender, &proxy, &player, Some(position));
+                    }
+
+                    true
+                },
+            );
+        }
+    }
+
+    /// Bus names of all MPRIS players on the session bus
+    fn list_players(connection: &Connection) -> Vec<String> {
+        let names: Result<(Vec<String>,), _> =
+            Self::bus_proxy(connection).method_call(DBUS_NAME, "ListNames", ());
+
+        match names {
+            Ok((names,)) => names
+                .into_iter()
+                .filter(|name| name.starts_with(MPRIS_BUS_NAME_PREFIX))
+                .collect(),
+            Err(error) => {
+                println!("player_event_producer: Couldn't list players: {:?}", error);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Starts tracking `player` and sends its current state
+    fn attach_player(
+        sender: &Sender<PlayerEvent>,
+        connection: &Connection,
+        owners: &PlayerOwners,
+        player: String,
+    ) {
+        let owner: Result<(String,), _> =
+            Self::bus_proxy(connection).method_call(DBUS_NAME, "GetNameOwner", (player.as_str(),));
+
+        match owner {
+            Ok((owner,)) => {
+                owners.lock().unwrap().insert(owner, player.clone());
+            }
+            Err(error) => {
+                println!("player_event_producer: {} has no owner: {:?}", player, error);
+                return;
+            }
+        }
+
+        Self::send(sender, &player, PlayerEventKind::Connected);
+
+        let proxy = Self::player_proxy(connection, &player);
+
+        let metadata_res: Result<arg::Variant<Box<dyn arg::RefArg>>, _> =
+            proxy.get(PLAYER_INTERFACE, "Metadata");
+
+        match metadata_res {
+            Ok(metadata) => Self::send_song(sender, &player, TrackMetadata::decode(&metadata)),
+            Err(error) => println!("player_event_producer: Couldn't read metadata of {}: {:?}", player, error),
+        }
+
+        Self::send_position(sender, &proxy, &player, None);
+    }
+
+    fn player_of(owners: &PlayerOwners, message: &Message) -> Option<String> {
+        let owner = message.sender()?;
+        owners.lock().unwrap().get(&*owner).cloned()
+    }
+
+    fn bus_proxy(connection: &Connection) -> Proxy<'_, &Connection> {
+        connection.with_proxy(
+            DBUS_NAME,
+            DBUS_PATH,
+            Duration::from_millis(5000),
+        )
+    }
+
+    fn player_proxy<'a>(connection: &'a Connection, player: &'a str) -> Proxy<'a, &'a Connection> {
+        connection.with_proxy(
+            player,
+            MPRIS_PATH,
+            Duration::from_millis(5000),
+        )
+    }
+
+    fn send(sender: &Sender<PlayerEvent>, player: &str, kind: PlayerEventKind) {
+        if let Err(error) = sender.send(PlayerEvent {
+            player: player.to_string(),
+            kind,
+        }) {
+            println!("{:?}", error);
+        }
+    }
+
+    fn send_song(
+        sender: &Sender<PlayerEvent>,
+        player: &str,
+        metadata: Result<TrackMetadata, MetadataError>,
+    ) {
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                println!("player_event_producer: {} sent unusable metadata: {}", player, error);
+                return;
+            }
+        };
+
+        println!("player_event_producer: {} {:#?} - {:#?}", player, metadata.title, metadata.artists);
+
+        Self::send(sender, player, PlayerEventKind::SongChanged(metadata));
+    }
+
+    /// Reads the playback state of the player, `position` is used instead of the player's if given
+    fn send_position(
+        sender: &Sender<PlayerEvent>,
+        proxy: &Proxy<&Connection>,
+        player: &str,
+        position: Option<Duration>,
+    ) {
+        let properties = match proxy.get_all(PLAYER_INTERFACE) {
+            Ok(properties) => properties,
+            Err(error) => {
+                println!("player_event_producer: Couldn't read playback state of {}: {:?}", player, error);
+                return;
+            }
+        };
+
+        let status = properties
+            .get("PlaybackStatus")
+            .and_then(|status| status.as_str())
+            .and_then(PlaybackStatus::from_mpris)
+            .unwrap_or(PlaybackStatus::Stopped);
+        // Not every player implements the optional `Rate`
+        let rate = properties
+            .get("Rate")
+            .and_then(|rate| rate.as_f64())
+            .unwrap_or(1.0);
+        let position = position.unwrap_or_else(|| {
+            let micros = properties
+                .get("Position")
+                .and_then(|position| position.as_i64())
+                .unwrap_or(0);
+            Duration::from_micros(micros.max(0) as u64)
+        });
+
+        Self::send(sender, player, PlayerEventKind::PositionChanged(
+            PositionClock::new(position, status, rate)
+        ));
+    }
+}
+
+struct PropertiesChanged {
+    /// `None` if the metadata didn't change
+    metadata: Option<Result<TrackMetadata, MetadataError>>,
+    /// True if any property affecting the playback position changed
+    playback_changed: bool,
+}
+
+impl arg::ReadAll for PropertiesChanged {
+    fn read(i: &mut arg::Iter) -> Result<Self, arg::TypeMismatchError> {
+        let _interface: String = i.read()?;
+        let changed_properties: HashMap<String, arg::Variant<Box<dyn arg::RefArg>>> =
+            i.read()?;
+
+        let playback_changed = ["Metadata", "PlaybackStatus", "Rate"]
+            .iter()
+            .any(|property| changed_properties.contains_key(*property));
+
+        Ok(PropertiesChanged {
+            metadata: changed_properties
+                .get("Metadata")
+                .map(|metadata| TrackMetadata::decode(metadata)),
+            playback_changed,
+        })
+    }
+}
+
+impl dbus::message::SignalArgs for PropertiesChanged {
+    const NAME: &'static str = "PropertiesChanged";
+    const INTERFACE: &'static str = "org.freedesktop.DBus.Properties";
+}
+
+struct Seeked {
+    /// In microseconds
+    position: i64,
+}
+
+impl arg::ReadAll for Seeked {
+    fn read(i: &mut arg::Iter) -> Result<Self, arg::TypeMismatchError> {
+        Ok(Seeked { position: i.read()? })
+    }
+}
+
+impl dbus::message::SignalArgs for Seeked {
+    const NAME: &'static str = "Seeked";
+    const INTERFACE: &'static str = PLAYER_INTERFACE;
+}
+
+struct NameOwnerChanged {
+    name: String,
+    /// Empty if the name is acquired
+    old_owner: String,
+    /// Empty if the name is released
+    new_owner: String,
+}
+
+impl arg::ReadAll for NameOwnerChanged {
+    fn read(i: &mut arg::Iter) -> Result<Self, arg::TypeMismatchError> {
+        Ok(NameOwnerChanged {
+            name: i.read()?,
+            old_owner: i.read()?,
+            new_owner: i.read()?,
+        })
+    }
+}
+
+impl dbus::message::SignalArgs for NameOwnerChanged {
+    const NAME: &'static str = "NameOwnerChanged";
+    const INTERFACE: &'static str = DBUS_NAME;
+}
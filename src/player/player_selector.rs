@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use super::{
+    player_event::{player_name, PlayerEvent, PlayerEventKind},
+    position_clock::{PlaybackStatus, PositionClock},
+};
+
+const ANY_PLAYER: &'static str = "any";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerSelection {
+    Any,
+    /// Name of the player as returned by `player_name`
+    Player(String),
+}
+
+impl PlayerSelection {
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "" | ANY_PLAYER => PlayerSelection::Any,
+            name => PlayerSelection::Player(name.to_string()),
+        }
+    }
+
+    pub fn to_setting(&self) -> &str {
+        match self {
+            PlayerSelection::Any => ANY_PLAYER,
+            PlayerSelection::Player(name) => name,
+        }
+    }
+
+    fn accepts(&self, player: &str) -> bool {
+        match self {
+            PlayerSelection::Any => true,
+            PlayerSelection::Player(name) => player_name(player) == name,
+        }
+    }
+}
+
+/// Last known state of a player, so that it can be replayed when the player becomes active
+#[derive(Default)]
+struct TrackedPlayer {
+    song: Option<PlayerEventKind>,
+    clock: Option<PositionClock>,
+}
+
+impl TrackedPlayer {
+    fn is_playing(&self) -> bool {
+        matches!(self.clock, Some(clock) if clock.status() == PlaybackStatus::Playing)
+    }
+}
+
+/// Tracks every player independently and decides whose events reach the rest of the app.
+/// With `PlayerSelection::Any` the player that most recently started playing is followed.
+pub struct PlayerSelector {
+    selection: PlayerSelection,
+    players: HashMap<String, TrackedPlayer>,
+    active: Option<String>,
+}
+
+impl PlayerSelector {
+    pub fn new(selection: PlayerSelection) -> Self {
+        PlayerSelector {
+            selection,
+            players: HashMap::new(),
+            active: None,
+        }
+    }
+
+    /// Records `event` and returns the events the app should receive because of it
+    pub fn handle(&mut self, event: PlayerEvent) -> Vec<PlayerEvent> {
+        if let PlayerEventKind::Disconnected = event.kind {
+            return self.remove(event);
+        }
+
+        let player = self.players.entry(event.player.clone()).or_default();
+
+        match &event.kind {
+            PlayerEventKind::SongChanged(_) => player.song = Some(event.kind.clone()),
+            PlayerEventKind::PositionChanged(clock) => player.clock = Some(*clock),
+            PlayerEventKind::Connected | PlayerEventKind::Disconnected => (),
+        }
+
+        let is_playing = player.is_playing();
+
+        if !self.selection.accepts(&event.player) {
+            return Vec::new();
+        }
+
+        match &self.active {
+            Some(active) if *active == event.player => vec![event],
+            Some(_) if is_playing => self.activate(event.player),
+            Some(_) => Vec::new(),
+            None => self.activate(event.player),
+        }
+    }
+
+    /// Switches to `selection`, returns the state of the newly followed player if there is one.
+    /// The previously followed player is disconnected if no player replaces it.
+    pub fn select(&mut self, selection: PlayerSelection) -> Vec<PlayerEvent> {
+        self.selection = selection;
+        let previous = self.active.take();
+
+        let selection = &self.selection;
+        let candidate = self.players
+            .iter()
+            .filter(|(player, _)| selection.accepts(player))
+            .max_by_key(|(_, tracked)| (tracked.is_playing(), tracked.song.is_some()))
+            .map(|(player, _)| player.clone());
+
+        match (candidate, previous) {
+            (Some(player), _) => self.activate(player),
+            (None, Some(previous)) => vec![PlayerEvent {
+                player: previous,
+                kind: PlayerEventKind::Disconnected,
+            }],
+            (None, None) => Vec::new(),
+        }
+    }
+
+    /// Names of the known players, sorted and without duplicates
+    pub fn player_names(&self) -> Vec<String> {
+        let mut names = self.players
+            .keys()
+            .map(|player| player_name(player).to_string())
+            .collect::<Vec<_>>();
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Forgets a closed player, the next best player is followed if it was the followed one
+    fn remove(&mut self, event: PlayerEvent) -> Vec<PlayerEvent> {
+        self.players.remove(&event.player);
+
+        if self.active.as_ref() != Some(&event.player) {
+            return Vec::new();
+        }
+        // It is disconnected by `event` already
+        self.active = None;
+
+        let mut events = vec![event];
+        events.extend(self.select(self.selection.clone()));
+        events
+    }
+
+    fn activate(&mut self, player: String) -> Vec<PlayerEvent> {
+        let tracked = &self.players[&player];
+
+        let events = std::iter::once(PlayerEventKind::Connected)
+            .chain(tracked.song.iter().cloned())
+            .chain(tracked.clock.map(PlayerEventKind::PositionChanged))
+            .map(|kind| PlayerEvent {
+                player: player.clone(),
+                kind,
+            })
+            .collect();
+
+        self.active = Some(player);
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::player::{
+        metadata::TrackMetadata,
+        player_event::{PlayerEvent, PlayerEventKind},
+        position_clock::{PlaybackStatus, PositionClock},
+    };
+
+    use super::{PlayerSelection, PlayerSelector};
+
+    fn song(player: &str, song_name: &str) -> PlayerEvent {
+        PlayerEvent {
+            player: player.to_string(),
+            kind: PlayerEventKind::SongChanged(TrackMetadata {
+                title: song_name.to_string(),
+                artists: vec!["Tool".to_string()],
+                ..TrackMetadata::default()
+            }),
+        }
+    }
+
+    fn position(player: &str, status: PlaybackStatus) -> PlayerEvent {
+        PlayerEvent {
+            player: player.to_string(),
+            kind: PlayerEventKind::PositionChanged(PositionClock::new(Duration::ZERO, status, 1.0)),
+        }
+    }
+
+    const SPOTIFY: &str = "org.mpris.MediaPlayer2.spotify";
+    const FIREFOX: &str = "org.mpris.MediaPlayer2.firefox.instance_1_84";
+
+    #[test]
+    fn any_follows_the_player_that_starts_playing() {
+        let mut selector = PlayerSelector::new(PlayerSelection::Any);
+
+        assert_eq!(selector.handle(song(SPOTIFY, "Pneuma")).len(), 2);
+        assert!(selector.handle(song(FIREFOX, "Invincible")).is_empty());
+
+        let events = selector.handle(position(FIREFOX, PlaybackStatus::Playing));
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|event| event.player == FIREFOX));
+
+        assert!(selector.handle(position(SPOTIFY, PlaybackStatus::Paused)).is_empty());
+        assert_eq!(selector.player_names(), vec!["firefox", "spotify"]);
+    }
+
+    #[test]
+    fn selected_player_is_replayed() {
+        let mut selector = PlayerSelector::new(PlayerSelection::Player("spotify".to_string()));
+
+        assert!(selector.handle(song(FIREFOX, "Invincible")).is_empty());
+        assert_eq!(selector.handle(song(SPOTIFY, "Pneuma")).len(), 2);
+
+        let events = selector.select(PlayerSelection::Player("firefox".to_string()));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].player, FIREFOX);
+
+        // The selected player isn't running
+        let events = selector.select(PlayerSelection::Player("vlc".to_string()));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].player, FIREFOX);
+        assert!(matches!(events[0].kind, PlayerEventKind::Disconnected));
+        assert!(selector.select(PlayerSelection::Player("mpv".to_string())).is_empty());
+    }
+
+    #[test]
+    fn closing_the_followed_player_moves_on() {
+        let mut selector = PlayerSelector::new(PlayerSelection::Any);
+
+        selector.handle(song(SPOTIFY, "Pneuma"));
+        selector.handle(song(FIREFOX, "Invincible"));
+
+        let events = selector.handle(PlayerEvent {
+            player: SPOTIFY.to_string(),
+            kind: PlayerEventKind::Disconnected,
+        });
+        assert!(matches!(events[0].kind, PlayerEventKind::Disconnected));
+        assert!(matches!(events[1].kind, PlayerEventKind::Connected));
+        assert_eq!(events[1].player, FIREFOX);
+        assert_eq!(selector.player_names(), vec!["firefox"]);
+
+        let events = selector.handle(PlayerEvent {
+            player: FIREFOX.to_string(),
+            kind: PlayerEventKind::Disconnected,
+        });
+        assert_eq!(events.len(), 1);
+    }
+}
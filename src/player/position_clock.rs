@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+impl PlaybackStatus {
+    /// Parses the `PlaybackStatus` property of `org.mpris.MediaPlayer2.Player`
+    pub fn from_mpris(status: &str) -> Option<Self> {
+        match status {
+            "Playing" => Some(PlaybackStatus::Playing),
+            "Paused" => Some(PlaybackStatus::Paused),
+            "Stopped" => Some(PlaybackStatus::Stopped),
+            _ => None,
+        }
+    }
+}
+
+/// Playback position reported by the player at `updated_at`.
+/// Players only report the position when it jumps, so it is advanced locally in between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionClock {
+    position: Duration,
+    updated_at: Instant,
+    status: PlaybackStatus,
+    rate: f64,
+}
+
+impl PositionClock {
+    pub fn new(position: Duration, status: PlaybackStatus, rate: f64) -> Self {
+        PositionClock {
+            position,
+            updated_at: Instant::now(),
+            status,
+            rate,
+        }
+    }
+
+    pub fn position(&self) -> Duration {
+        self.position_at(Instant::now())
+    }
+
+    pub fn position_at(&self, now: Instant) -> Duration {
+        match self.status {
+            PlaybackStatus::Playing if self.rate > 0.0 => {
+                let elapsed = now.saturating_duration_since(self.updated_at);
+                self.position + elapsed.mul_f64(self.rate)
+            }
+            _ => self.position,
+        }
+    }
+
+    pub fn status(&self) -> PlaybackStatus {
+        self.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{PlaybackStatus, PositionClock};
+
+    #[test]
+    fn interpolates_while_playing() {
+        let clock = PositionClock::new(Duration::from_secs(10), PlaybackStatus::Playing, 1.5);
+        let later = clock.updated_at + Duration::from_secs(2);
+
+        assert_eq!(clock.position_at(later), Duration::from_secs(13));
+    }
+
+    #[test]
+    fn stands_still_while_paused() {
+        let clock = PositionClock::new(Duration::from_secs(10), PlaybackStatus::Paused, 1.0);
+
+        assert_eq!(clock.position_at(Instant::now() + Duration::from_secs(5)), Duration::from_secs(10));
+    }
+}
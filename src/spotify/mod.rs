@@ -1,2 +0,0 @@
-pub mod spotify_event;
-pub mod spotify_event_producer;
\ No newline at end of file
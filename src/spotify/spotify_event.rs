@@ -1,7 +0,0 @@
-#[derive(Debug, Clone)]
-pub enum SpotifyEvent {
-    SongChanged {
-        song_name: String,
-        artist_name: String,
-    }
-}
\ No newline at end of file
@@ -1,13 +1,38 @@
+use crate::{lyrics::{cache::CacheStats, Lyrics, LyricsOrigin}, player::{metadata::TrackMetadata, position_clock::PositionClock}};
+
 #[derive(Debug)]
 pub enum UIEvent {
     SongInformationFetched {
-        song_name: String,
-        artist_name: String,
-        lyrics: String,
+        metadata: TrackMetadata,
+        lyrics: Lyrics,
         cover_art: Vec<u8>,
+        provider: String,
+        origin: LyricsOrigin,
     },
     FetchingLyrics {
-        song_name: String,
-        artist_name: String,
+        metadata: TrackMetadata,
     },
+    SongNotFound {
+        metadata: TrackMetadata,
+    },
+    NetworkFailed {
+        metadata: TrackMetadata,
+    },
+    PositionChanged(PositionClock),
+    PlayerConnected {
+        player_name: String,
+    },
+    PlayerDisconnected,
+    /// Names of the known players
+    PlayersChanged(Vec<String>),
+    CacheStats(CacheStats),
+}
+
+/// Requests from the UI to the lyrics fetcher, which owns the cache
+#[derive(Debug, Clone, Copy)]
+pub enum CacheCommand {
+    Clear,
+    /// Forgets the lyrics of the current song and fetches them again
+    RefetchCurrentSong,
+    ShowStats,
 }
\ No newline at end of file
@@ -1,17 +1,39 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crossbeam_channel::Sender;
 use gdk::prelude::{IsA};
+use gio::prelude::{ListModelExt, SettingsExt};
 use gtk::{
     Align, Image, ToggleButton,
     Widget, Label,
 };
 use gtk::prelude::{ButtonExt, GtkWindowExt, WidgetExt};
 
+use crate::{configs, player::player_selector::PlayerSelection};
+
 #[derive(Clone)]
 pub struct HeaderBar {
     pub container: gtk::HeaderBar,
+    player_dropdown: gtk::DropDown,
+    /// First item is "Any player", the rest are player names
+    player_list: gtk::StringList,
+    player_selection: Rc<RefCell<PlayerSelection>>,
+    /// Set while the list is rebuilt so that it isn't taken as the user choosing
+    updating_player_list: Rc<Cell<bool>>,
 }
 
 impl HeaderBar {
-    pub fn new(window: impl GtkWindowExt) -> Self {
+    const ANY_PLAYER_LABEL: &'static str = "Any player";
+    pub const REFETCH_SONG_ACTION: &'static str = "refetch-song";
+    pub const CLEAR_CACHE_ACTION: &'static str = "clear-cache";
+    pub const CACHE_STATS_ACTION: &'static str = "cache-stats";
+
+    pub fn new(
+        window: impl GtkWindowExt,
+        player_selection_sender: Sender<PlayerSelection>,
+        player_selection: PlayerSelection,
+    ) -> Self {
         let headerbar = gtk::HeaderBar::new();
 
         headerbar.set_title_widget(Some(&Label::new(Some("SyncLyrics"))));
@@ -19,12 +41,104 @@ impl HeaderBar {
 
         headerbar.pack_start(&Self::create_pin_toggle(window));
 
+        let player_list = gtk::StringList::new(&[Self::ANY_PLAYER_LABEL]);
+        let player_dropdown = gtk::DropDown::builder()
+            .model(&player_list)
+            .tooltip_text("Player to display the lyrics of")
+            .valign(Align::Center)
+            .build();
+
+        headerbar.pack_end(&Self::create_menu_button());
+        headerbar.pack_end(&player_dropdown);
+
         // headerbar.pack_end(&Self::create_switch(style_manager.clone()));
         headerbar.set_widget_name("headerbar");
 
-        HeaderBar {
+        let headerbar = HeaderBar {
             container: headerbar,
+            player_dropdown,
+            player_list,
+            player_selection: Rc::new(RefCell::new(player_selection)),
+            updating_player_list: Rc::new(Cell::new(false)),
+        };
+
+        headerbar.set_players(&[]);
+        headerbar.connect_player_selected(player_selection_sender);
+
+        headerbar
+    }
+
+    /// Lists `player_names` in the player chooser, the selected player is kept even if it is not running
+    pub fn set_players(&self, player_names: &[String]) {
+        let selection = self.player_selection.borrow().clone();
+
+        let mut names = player_names.to_vec();
+        if let PlayerSelection::Player(name) = &selection {
+            if !names.contains(name) {
+                names.push(name.clone());
+                names.sort();
+            }
         }
+
+        let items = std::iter::once(Self::ANY_PLAYER_LABEL)
+            .chain(names.iter().map(|name| name.as_str()))
+            .collect::<Vec<_>>();
+
+        let selected = match &selection {
+            PlayerSelection::Any => 0,
+            PlayerSelection::Player(name) => names
+                .iter()
+                .position(|other| other == name)
+                .map_or(0, |index| index + 1),
+        };
+
+        self.updating_player_list.set(true);
+        self.player_list.splice(0, self.player_list.n_items(), &items);
+        self.player_dropdown.set_selected(selected as u32);
+        self.updating_player_list.set(false);
+    }
+
+    fn connect_player_selected(&self, player_selection_sender: Sender<PlayerSelection>) {
+        let player_list = self.player_list.clone();
+        let player_selection = self.player_selection.clone();
+        let updating_player_list = self.updating_player_list.clone();
+
+        self.player_dropdown.connect_selected_notify(move |dropdown| {
+            if updating_player_list.get() {
+                return;
+            }
+
+            let selection = match dropdown.selected() {
+                0 => PlayerSelection::Any,
+                index => match player_list.string(index) {
+                    Some(name) => PlayerSelection::Player(name.to_string()),
+                    None => return,
+                },
+            };
+
+            if let Some(settings) = configs::settings() {
+                if let Err(error) = settings.set_string(configs::SELECTED_PLAYER_KEY, selection.to_setting()) {
+                    println!("headerbar: Couldn't save the selected player: {:?}", error);
+                }
+            }
+
+            *player_selection.borrow_mut() = selection.clone();
+            player_selection_sender.send(selection).unwrap();
+        });
+    }
+
+    /// The items activate the window actions added by the application
+    fn create_menu_button() -> impl IsA<Widget> {
+        let menu = gtk::gio::Menu::new();
+        menu.append(Some("Fetch Lyrics Again"), Some(&format!("win.{}", Self::REFETCH_SONG_ACTION)));
+        menu.append(Some("Lyrics Cache Usage"), Some(&format!("win.{}", Self::CACHE_STATS_ACTION)));
+        menu.append(Some("Clear Lyrics Cache"), Some(&format!("win.{}", Self::CLEAR_CACHE_ACTION)));
+
+        gtk::MenuButton::builder()
+            .icon_name("open-menu-symbolic")
+            .menu_model(&menu)
+            .valign(Align::Center)
+            .build()
     }
 
     fn create_pin_toggle(_window: impl GtkWindowExt) -> impl IsA<Widget> {
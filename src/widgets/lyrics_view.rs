@@ -1,32 +1,66 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
 use gdk::gdk_pixbuf::{Pixbuf, PixbufLoader};
 use gdk::prelude::{PixbufLoaderExt, IsA};
 use gdk::pango::EllipsizeMode;
+use glib::Continue;
 
-use gtk::prelude::{BoxExt};
-use gtk::traits::{WidgetExt, StyleContextExt, OrientableExt, GridExt};
-use gtk::{Widget, IconSize};
+use gtk::prelude::{BoxExt, Cast, EventControllerExt, GestureDragExt, WidgetExtManual};
+use gtk::traits::{WidgetExt, StyleContextExt, OrientableExt, GridExt, AdjustmentExt};
+use gtk::{Widget, IconSize, Inhibit};
 use image::ImageOutputFormat;
 
+use crate::lyrics::{Lyrics, LyricsOrigin, lrc::{SyncedLyrics, SyncedLine, SyncedWord}, sections::LyricsSection};
+use crate::player::metadata::TrackMetadata;
+
 #[derive(Clone)]
 pub struct LyricsView {
     container: gtk::Box,
     title_label: gtk::Label,
     artist_label: gtk::Label,
+    /// Where the lyrics came from, and whether they are shown offline
+    source_label: gtk::Label,
     cover_image: gtk::Image,
     background_image: gtk::Picture,
     lyrics_label: gtk::Label,
     spinner: gtk::Spinner,
     stack: gtk::Stack,
     song_not_found_subtitle_label: gtk::Label,
+    waiting_subtitle_label: gtk::Label,
+    synced_lines_box: gtk::Box,
+    synced_scroller: gtk::ScrolledWindow,
+    sections_box: gtk::Box,
+    sections_scroller: gtk::ScrolledWindow,
+    synced_state: Rc<RefCell<SyncedState>>,
+    /// Auto-follow is suspended until this instant after the user scrolls
+    follow_resumes_at: Rc<Cell<Option<Instant>>>,
 }
 
+#[derive(Default)]
+struct SyncedState {
+    lyrics: Option<SyncedLyrics>,
+    labels: Vec<gtk::Label>,
+    current_line: Option<usize>,
+    current_word: Option<usize>,
+}
+
+const NOT_CONNECTED_VIEW_NAME: &'static str = "connecting";
+const WAITING_VIEW_NAME: &'static str = "waiting";
 const NETWORK_ERROR_VIEW_NAME: &'static str = "network_error";
 const SONG_NOT_FOUND_NAME: &'static str = "song_not_found";
+const LYRICS_VIEW_NAME: &'static str = "lyrics";
+const SYNCED_LYRICS_VIEW_NAME: &'static str = "synced_lyrics";
+const SECTIONED_LYRICS_VIEW_NAME: &'static str = "sectioned_lyrics";
 
 impl LyricsView {
 
     const COVER_IMAGE_SIZE: i32 = 75;
     const NO_COVER_ICON_NAME: &'static str = "folder-music-symbolic";
+    const AUTO_FOLLOW_RESUME_DELAY: Duration = Duration::from_secs(4);
+    /// Fraction of the remaining distance scrolled at each frame
+    const AUTO_SCROLL_EASING: f64 = 0.15;
 
     pub fn new() -> Self {
         let top_overlay = gtk::Overlay::new();
@@ -63,8 +97,16 @@ impl LyricsView {
         let text_container = gtk::Box::new(gtk::Orientation::Vertical, 5);
         text_container.set_margin_top(15);
 
+        let source_label = gtk::Label::new(None);
+        source_label.set_halign(gtk::Align::Start);
+        source_label.set_margin_start(15);
+        source_label.style_context().add_class("dim-label");
+        source_label.style_context().add_class("caption");
+        source_label.set_visible(false);
+
         text_container.append(&title_label);
         text_container.append(&artist_label);
+        text_container.append(&source_label);
 
         top_container.append(&text_container);
 
@@ -92,8 +134,16 @@ impl LyricsView {
 
         // TODO: get welcome screen, etc.
 
-        // Open spotify screen
-        stack.add_named(&Self::get_not_connected_view(), Some("connecting"));
+        // Open a player screen
+        stack.add_named(&Self::get_not_connected_view(), Some(NOT_CONNECTED_VIEW_NAME));
+
+        let waiting_subtitle_label = gtk::Label::new(None);
+        let waiting_view = Self::title_with_subtitle_from_labels(
+            &gtk::Label::new(Some("Nothing is playing")),
+            &waiting_subtitle_label.clone()
+        );
+
+        stack.add_named(&waiting_view, Some(WAITING_VIEW_NAME));
         
         let network_error_view = Self::title_with_subtitle("Network error", "Check your internet connection");
 
@@ -121,30 +171,65 @@ impl LyricsView {
         label_scroller.set_vexpand(true);
         label_scroller.set_child(Some(&lyrics_label));
 
-        stack.add_named(&label_scroller, Some("lyrics"));
+        stack.add_named(&label_scroller, Some(LYRICS_VIEW_NAME));
+
+        // Synced lyrics, one label per line
+        let synced_lines_box = gtk::Box::new(gtk::Orientation::Vertical, 10);
+        synced_lines_box.set_margin_top(10);
+        synced_lines_box.set_margin_bottom(10);
+
+        let synced_scroller = gtk::ScrolledWindow::new();
+        synced_scroller.set_vexpand(true);
+        synced_scroller.set_child(Some(&synced_lines_box));
+
+        stack.add_named(&synced_scroller, Some(SYNCED_LYRICS_VIEW_NAME));
+
+        // Sectioned lyrics, a header and the lines of each section
+        let sections_box = gtk::Box::new(gtk::Orientation::Vertical, 15);
+        sections_box.set_margin_bottom(10);
+
+        let sections_scroller = gtk::ScrolledWindow::new();
+        sections_scroller.set_vexpand(true);
+        sections_scroller.set_child(Some(&sections_box));
+
+        stack.add_named(&sections_scroller, Some(SECTIONED_LYRICS_VIEW_NAME));
 
         container.append(&stack);
 
-        LyricsView {
+        let lyrics_view = LyricsView {
             container,
             title_label,
             artist_label,
+            source_label,
             cover_image,
             background_image,
             lyrics_label,
             spinner,
             stack,
             song_not_found_subtitle_label,
-        }
+            waiting_subtitle_label,
+            synced_lines_box,
+            synced_scroller,
+            sections_box,
+            sections_scroller,
+            synced_state: Rc::new(RefCell::new(SyncedState::default())),
+            follow_resumes_at: Rc::new(Cell::new(None)),
+        };
+
+        lyrics_view.init_auto_follow();
+
+        lyrics_view
     }
 
     pub fn as_widget(&self) -> &impl IsA<Widget> {
         &self.container
     }
 
-    pub fn song_changed(&mut self, song_title: &str, artist_name: &str) {
-        self.set_song_title(song_title);
-        self.set_artist(artist_name);
+    pub fn song_changed(&mut self, metadata: &TrackMetadata) {
+        self.set_song_title(&metadata.title);
+        self.set_artist(&metadata.artists.join(", "));
+        self.title_label.set_tooltip_text(metadata.album.as_deref());
+        self.source_label.set_visible(false);
         self.cover_image.set_from_icon_name(Some(Self::NO_COVER_ICON_NAME));
         self.background_image.set_visible(false);
 
@@ -152,13 +237,84 @@ impl LyricsView {
         self.stack.set_visible_child_name("spinner");
     }
 
-    pub fn song_data_retrieved(&mut self, lyrics: &str, cover_art: Option<&[u8]>) {
-        self.set_lyrics(lyrics);
+    pub fn song_data_retrieved(&mut self, lyrics: &Lyrics, cover_art: Option<&[u8]>, provider: &str, origin: LyricsOrigin) {
+        let view_name = match lyrics {
+            Lyrics::Plain(lyrics) => {
+                self.set_lyrics(lyrics);
+                LYRICS_VIEW_NAME
+            }
+            Lyrics::Synced(lyrics) => {
+                self.set_synced_lyrics(lyrics);
+                SYNCED_LYRICS_VIEW_NAME
+            }
+            Lyrics::Sectioned(sections) => {
+                self.set_sectioned_lyrics(sections);
+                SECTIONED_LYRICS_VIEW_NAME
+            }
+        };
         if let Some(cover_art) = cover_art {
             self.set_cover_art(cover_art);
         }
+        self.set_source(provider, origin);
         self.spinner.stop();
-        self.stack.set_visible_child_name("lyrics");
+        self.stack.set_visible_child_name(view_name);
+    }
+
+    /// Highlights the line at `position` of the track, does nothing unless synced lyrics are shown
+    pub fn set_position(&self, position: Duration) {
+        let mut state = self.synced_state.borrow_mut();
+        let state = &mut *state;
+
+        let lyrics = match &state.lyrics {
+            Some(lyrics) => lyrics,
+            None => return,
+        };
+
+        let current_line = lyrics.line_index_at(position);
+        let current_word = current_line.and_then(|index| {
+            lyrics.lines[index].word_index_at(lyrics.apply_offset(position))
+        });
+
+        if current_line == state.current_line && current_word == state.current_word {
+            return;
+        }
+
+        if current_line != state.current_line {
+            for (index, label) in state.labels.iter().enumerate() {
+                let is_past = matches!(current_line, Some(current) if index < current);
+
+                if is_past {
+                    label.style_context().add_class("dim-label");
+                } else {
+                    label.style_context().remove_class("dim-label");
+                }
+            }
+            if let Some(previous) = state.current_line {
+                state.labels[previous].set_markup(&Self::line_markup(&lyrics.lines[previous], None));
+            }
+        }
+
+        if let Some(current) = current_line {
+            state.labels[current].set_markup(
+                &Self::line_markup(&lyrics.lines[current], Some(current_word))
+            );
+        }
+
+        state.current_line = current_line;
+        state.current_word = current_word;
+    }
+
+    pub fn not_connected(&mut self) {
+        self.clear_song();
+        self.stack.set_visible_child_name(NOT_CONNECTED_VIEW_NAME);
+    }
+
+    pub fn player_connected(&mut self, player_name: &str) {
+        self.clear_song();
+        self.waiting_subtitle_label.set_label(
+            &format!("Play a song on {}", player_name)
+        );
+        self.stack.set_visible_child_name(WAITING_VIEW_NAME);
     }
 
     pub fn network_failed(&mut self) {
@@ -166,16 +322,212 @@ impl LyricsView {
         self.stack.set_visible_child_name(NETWORK_ERROR_VIEW_NAME);
     }
 
-    pub fn song_not_found(&mut self, song_title: &str, artist_name: &str) {
+    pub fn song_not_found(&mut self, metadata: &TrackMetadata) {
         self.spinner.stop();
         self.song_not_found_subtitle_label.set_label(
-            &format!("{} - {} could not be found", song_title, artist_name)  
+            &format!("{} - {} could not be found", metadata.title, metadata.artists.join(", "))  
         );
         self.stack.set_visible_child_name(SONG_NOT_FOUND_NAME);
     }
 
+    fn set_synced_lyrics(&mut self, lyrics: &SyncedLyrics) {
+        self.clear_synced_lyrics();
+        let mut state = self.synced_state.borrow_mut();
+
+        for line in &lyrics.lines {
+            let label = gtk::Label::new(None);
+            label.set_halign(gtk::Align::Start);
+            label.set_wrap(true);
+            label.set_margin_start(15);
+            label.set_margin_end(15);
+            label.set_markup(&Self::line_markup(line, None));
+
+            self.synced_lines_box.append(&label);
+            state.labels.push(label);
+        }
+
+        state.lyrics = Some(lyrics.clone());
+
+        self.follow_resumes_at.set(None);
+        self.synced_scroller.vadjustment().set_value(0.0);
+    }
+
+    /// Forgets the shown synced lyrics so that positions don't highlight them anymore
+    fn clear_synced_lyrics(&mut self) {
+        let mut state = self.synced_state.borrow_mut();
+
+        for label in state.labels.drain(..) {
+            self.synced_lines_box.remove(&label);
+        }
+        *state = SyncedState::default();
+    }
+
+    /// Repeated choruses are collapsed, they can be expanded to be read again
+    fn set_sectioned_lyrics(&mut self, sections: &[LyricsSection]) {
+        self.clear_synced_lyrics();
+        while let Some(child) = self.sections_box.first_child() {
+            self.sections_box.remove(&child);
+        }
+
+        let repeats = LyricsSection::repeats(sections);
+        for (section, repeat_of) in sections.iter().zip(repeats) {
+            // Sections that only name an earlier one are shown with its lines
+            let lines = match repeat_of {
+                Some(earlier) if section.lines.is_empty() => &sections[earlier].lines,
+                _ => &section.lines,
+            };
+            let header = section
+                .name
+                .as_deref()
+                .map(|name| Self::section_header(name, &section.performers));
+
+            let section_widget: Widget = match (header, repeat_of) {
+                (Some(header), Some(_)) => {
+                    let expander = gtk::Expander::new(None);
+                    expander.set_label_widget(Some(&header));
+                    expander.set_child(Some(&Self::section_lines(lines)));
+                    expander.set_expanded(false);
+                    expander.upcast()
+                }
+                (header, _) => {
+                    let section_box = gtk::Box::new(gtk::Orientation::Vertical, 5);
+                    if let Some(header) = header {
+                        section_box.append(&header);
+                    }
+                    if !lines.is_empty() {
+                        section_box.append(&Self::section_lines(lines));
+                    }
+                    section_box.upcast()
+                }
+            };
+            section_widget.set_margin_start(15);
+            section_widget.set_margin_end(15);
+
+            self.sections_box.append(&section_widget);
+        }
+
+        self.sections_scroller.vadjustment().set_value(0.0);
+    }
+
+    /// `Chorus · Kendrick Lamar & Rihanna`, the performers are dimmed
+    fn section_header(name: &str, performers: &[String]) -> gtk::Label {
+        let mut markup = format!("<span weight=\"bold\">{}</span>", Self::escape_markup(name));
+        if !performers.is_empty() {
+            markup += &format!(
+                "<span alpha=\"60%\"> · {}</span>",
+                Self::escape_markup(&performers.join(" & "))
+            );
+        }
+
+        let label = gtk::Label::new(None);
+        label.set_halign(gtk::Align::Start);
+        label.set_wrap(true);
+        label.set_markup(&markup);
+        label.style_context().add_class("heading");
+        label
+    }
+
+    fn section_lines(lines: &[String]) -> gtk::Label {
+        let label = gtk::Label::new(None);
+        label.set_halign(gtk::Align::Start);
+        label.set_wrap(true);
+        label.set_markup(&format!(
+            "<span size=\"large\">{}</span>",
+            Self::escape_markup(&lines.join("\n"))
+        ));
+        label
+    }
+
+    /// `current_word` is `Some` for the line being sung, its words after the current one are dimmed
+    fn line_markup(line: &SyncedLine, current_word: Option<Option<usize>>) -> String {
+        let text = match (current_word, line.words.is_empty()) {
+            (Some(Some(current)), false) => {
+                let (sung, unsung) = line.words.split_at(current + 1);
+                let concat = |words: &[SyncedWord]| {
+                    words.iter().map(|word| word.text.as_str()).collect::<String>()
+                };
+
+                format!(
+                    "{}<span alpha=\"50%\">{}</span>",
+                    Self::escape_markup(concat(sung).trim_start()),
+                    Self::escape_markup(&concat(unsung))
+                )
+            }
+            // Instrumental breaks
+            _ if line.text.is_empty() => "♪".to_string(),
+            _ => Self::escape_markup(&line.text),
+        };
+
+        match current_word {
+            Some(_) => format!("<span size=\"large\" weight=\"bold\">{}</span>", text),
+            None => format!("<span size=\"large\">{}</span>", text),
+        }
+    }
+
+    /// Keeps the current line centered, unless the user has scrolled recently.
+    /// Only scroll and drag gestures are the user, the position also moves when the lyrics are laid out again.
+    fn init_auto_follow(&self) {
+        let scroll_controller = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+        scroll_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        {
+            let follow_resumes_at = self.follow_resumes_at.clone();
+
+            scroll_controller.connect_scroll(move |_, _, _| {
+                Self::suspend_auto_follow(&follow_resumes_at);
+                Inhibit(false)
+            });
+        }
+        self.synced_scroller.add_controller(&scroll_controller);
+
+        // Touch screens and the scrollbar
+        let drag_gesture = gtk::GestureDrag::new();
+        drag_gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
+        {
+            let follow_resumes_at = self.follow_resumes_at.clone();
+
+            drag_gesture.connect_drag_update(move |_, _, _| Self::suspend_auto_follow(&follow_resumes_at));
+        }
+        self.synced_scroller.add_controller(&drag_gesture);
+
+        let synced_state = self.synced_state.clone();
+        let synced_lines_box = self.synced_lines_box.clone();
+        let follow_resumes_at = self.follow_resumes_at.clone();
+
+        self.synced_scroller.add_tick_callback(move |scroller, _| {
+            if matches!(follow_resumes_at.get(), Some(resumes_at) if Instant::now() < resumes_at) {
+                return Continue(true);
+            }
+
+            let state = synced_state.borrow();
+            let adjustment = scroller.vadjustment();
+
+            let line_center = match state.current_line.and_then(|index| state.labels.get(index)) {
+                Some(label) => match label.translate_coordinates(&synced_lines_box, 0.0, 0.0) {
+                    Some((_, y)) => y + label.height() as f64 / 2.0,
+                    None => return Continue(true),
+                },
+                None => 0.0,
+            };
+
+            let max_value = (adjustment.upper() - adjustment.page_size()).max(adjustment.lower());
+            let target = (line_center - adjustment.page_size() / 2.0)
+                .clamp(adjustment.lower(), max_value);
+            let distance = target - adjustment.value();
+
+            if distance.abs() > 0.5 {
+                adjustment.set_value(adjustment.value() + distance * Self::AUTO_SCROLL_EASING);
+            }
+
+            Continue(true)
+        });
+    }
+
+    fn suspend_auto_follow(follow_resumes_at: &Cell<Option<Instant>>) {
+        follow_resumes_at.set(Some(Instant::now() + Self::AUTO_FOLLOW_RESUME_DELAY));
+    }
+
     fn get_not_connected_view() -> impl IsA<Widget> {
-        Self::title_with_subtitle("Spotify is not detected", "You should launch Spotify")
+        Self::title_with_subtitle("No player is detected", "You should launch Spotify or another media player")
     }
 
     fn title_with_subtitle(title: &str, subtitle: &str) -> impl IsA<Widget> {
@@ -210,6 +562,27 @@ impl LyricsView {
         content
     }
 
+    fn clear_song(&mut self) {
+        self.set_song_title("");
+        self.set_artist("");
+        self.title_label.set_tooltip_text(None);
+        self.source_label.set_visible(false);
+        self.cover_image.set_from_icon_name(Some(Self::NO_COVER_ICON_NAME));
+        self.background_image.set_visible(false);
+        self.clear_synced_lyrics();
+        self.spinner.stop();
+    }
+
+    fn set_source(&mut self, provider: &str, origin: LyricsOrigin) {
+        self.source_label.set_label(&match origin {
+            LyricsOrigin::Network => format!("Lyrics from {}", provider),
+            LyricsOrigin::Cache => format!("Lyrics from {} (cached)", provider),
+            LyricsOrigin::Offline => format!("Offline, cached lyrics from {}", provider),
+            LyricsOrigin::Local => format!("Local lyrics from {}", provider.to_lowercase()),
+        });
+        self.source_label.set_visible(true);
+    }
+
     fn set_song_title(&mut self, song_title: &str) {
         self.title_label.set_markup(&format!(
             "<span size=\"xx-large\">{}</span>",
@@ -225,6 +598,7 @@ impl LyricsView {
     }
 
     pub fn set_lyrics(&mut self, lyrics: &str) {
+        self.clear_synced_lyrics();
         self.lyrics_label.set_markup(&format!(
             "<span size=\"large\">{}</span>",
             Self::escape_markup(lyrics)
@@ -265,6 +639,6 @@ impl LyricsView {
     }
 
     fn escape_markup(text: &str) -> String {
-        text.replace("&", "&amp;")
+        glib::markup_escape_text(text).to_string()
     }
 }
@@ -0,0 +1,3 @@
+#!/bin/sh
+echo "Lyrics server is down" >&2
+exit 3
@@ -0,0 +1,12 @@
+#!/bin/sh
+# Has synced lyrics of Pneuma and plain lyrics of Invincible
+track=$(cat)
+
+case "$track" in
+    *'"title":"Pneuma"'*'"length_ms":713000'*)
+        printf '%s\n' '{"lyrics": "[01:52.30]We are born of one breath\n[01:58.91]One word", "source_url": "https://lyrics.example.com/pneuma", "confidence": 0.9}' ;;
+    *'"title":"Invincible"'*)
+        printf '%s\n' '{"artist": "TOOL", "track": "Invincible", "lyrics": "Long ago\nA warrior"}' ;;
+    *)
+        echo null ;;
+esac
@@ -0,0 +1,138 @@
+#!/usr/bin/env python3
+"""Writes the tagged audio fixtures of src/lyrics/embedded.rs next to this script.
+
+The files have valid headers and tags but little or no audio.
+"""
+
+import os
+import struct
+
+PLAIN = "We are born of one breath\nOne word"
+LRC = "[01:52.30]We are born of one breath\n[01:58.91]One word"
+SYNCED = [(112300, "We are born of one breath"), (118910, "\nOne word")]
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+
+def write(name, data):
+    with open(os.path.join(HERE, name), "wb") as file:
+        file.write(data)
+
+
+# MP3
+
+
+def syncsafe(value):
+    return bytes((value >> shift) & 0x7F for shift in (21, 14, 7, 0))
+
+
+def id3_frame(frame_id, body):
+    return frame_id + syncsafe(len(body)) + b"\x00\x00" + body
+
+
+def id3v24(frames):
+    body = b"".join(frames)
+    return b"ID3\x04\x00\x00" + syncsafe(len(body)) + body
+
+
+def uslt(text):
+    return id3_frame(b"USLT", b"\x03eng\x00" + text.encode())
+
+
+def utf16(text):
+    return b"\xff\xfe" + text.encode("utf-16-le") + b"\x00\x00"
+
+
+def sylt(lines):
+    # UTF-16 like most taggers write, milliseconds, lyrics
+    body = b"\x01eng\x02\x01" + utf16("")
+    for start, text in lines:
+        body += utf16(text) + struct.pack(">I", start)
+    return id3_frame(b"SYLT", body)
+
+
+def mpeg_frame():
+    # MPEG 1 layer III, 128 kbit/s, 44.1 kHz, silent
+    return b"\xff\xfb\x90\x00" + bytes(413)
+
+
+# FLAC
+
+
+def vorbis_comment(comments):
+    vendor = b"SyncLyrics fixtures"
+    data = struct.pack("<I", len(vendor)) + vendor + struct.pack("<I", len(comments))
+    for comment in comments:
+        comment = comment.encode()
+        data += struct.pack("<I", len(comment)) + comment
+    return data
+
+
+def flac_block(block_type, body, is_last):
+    return bytes([block_type | (0x80 if is_last else 0)]) + struct.pack(">I", len(body))[1:] + body
+
+
+def flac(comments):
+    # 4096 sample blocks, 44.1 kHz, stereo, 16 bits, no samples
+    stream_info = struct.pack(">HH", 4096, 4096) + bytes(6) + bytes.fromhex("0ac442f000000000") + bytes(16)
+    blocks = flac_block(0, stream_info, False) + flac_block(4, vorbis_comment(comments), False)
+    padding = flac_block(1, bytes(16), True)
+    return b"fLaC" + blocks + padding
+
+
+# Ogg
+
+
+def ogg_crc(data):
+    crc = 0
+    for byte in data:
+        crc ^= byte << 24
+        for _ in range(8):
+            crc = ((crc << 1) ^ 0x04C11DB7) if crc & 0x80000000 else crc << 1
+            crc &= 0xFFFFFFFF
+    return crc
+
+
+def ogg_page(packet, sequence, header_type, serial=0x5EED):
+    lacing = [255] * (len(packet) // 255) + [len(packet) % 255]
+    header = b"OggS\x00" + bytes([header_type]) + struct.pack("<qII", 0, serial, sequence)
+    page = header + b"\x00\x00\x00\x00" + bytes([len(lacing)]) + bytes(lacing) + packet
+    return page[:22] + struct.pack("<I", ogg_crc(page)) + page[26:]
+
+
+def ogg(identification, comments):
+    return ogg_page(identification, 0, 0x02) + ogg_page(comments, 1, 0x00)
+
+
+def vorbis_identification():
+    return b"\x01vorbis" + struct.pack("<IBIiii", 0, 2, 44100, 0, 128000, 0) + b"\xb8\x01"
+
+
+def opus_head():
+    return b"OpusHead\x01\x02" + struct.pack("<HIhB", 312, 48000, 0, 0)
+
+
+# MP4
+
+
+def atom(name, body):
+    return struct.pack(">I", 8 + len(body)) + name + body
+
+
+def m4a(lyrics):
+    data = atom(b"data", struct.pack(">II", 1, 0) + lyrics.encode())
+    handler = atom(b"hdlr", bytes(8) + b"mdirappl" + bytes(9))
+    meta = atom(b"meta", bytes(4) + handler + atom(b"ilst", atom(b"\xa9lyr", data)))
+    moov = atom(b"moov", atom(b"udta", meta))
+    return atom(b"ftyp", b"M4A \x00\x00\x02\x00M4A isom") + atom(b"mdat", bytes(16)) + moov
+
+
+write("sylt.mp3", id3v24([sylt(SYNCED), uslt(PLAIN)]) + mpeg_frame())
+write("uslt.mp3", id3v24([uslt(PLAIN)]) + mpeg_frame())
+write("no_tag.mp3", mpeg_frame())
+write("lyrics.flac", flac(["TITLE=Pneuma", "LYRICS=" + PLAIN]))
+write("no_lyrics.flac", flac(["TITLE=Pneuma"]))
+# Long enough for the comment packet to span several segments
+write("lyrics.ogg", ogg(vorbis_identification(), b"\x03vorbis" + vorbis_comment(["TITLE=Pneuma", "COMMENT=" + "x" * 300, "lyrics=" + LRC]) + b"\x01"))
+write("lyrics.opus", ogg(opus_head(), b"OpusTags" + vorbis_comment(["UNSYNCEDLYRICS=" + PLAIN])))
+write("lyrics.m4a", m4a(PLAIN))
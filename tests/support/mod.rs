@@ -0,0 +1,3 @@
+//! Helpers shared by the tests, the unit tests of the crate include them by path
+
+pub mod stub_server;
@@ -0,0 +1,123 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread::spawn,
+};
+
+/// Replaced in response bodies by the address of the server, so that responses can link to other routes
+static BASE_URL_PLACEHOLDER: &'static str = "{base_url}";
+
+/// A local HTTP server with recorded responses, for testing providers without a network connection.
+/// It runs until the tests end.
+pub struct StubServer {
+    base_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl StubServer {
+    /// Serves the responses of `routes` by request path, others are not found
+    pub fn start(routes: Vec<(&str, StubResponse)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let routes = routes
+            .into_iter()
+            .map(|(path, response)| (path.to_string(), response.with_base_url(&base_url)))
+            .collect::<Vec<_>>();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let served_requests = requests.clone();
+        spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(error) = Self::serve(stream, &routes, &served_requests) {
+                    println!("stub_server: Couldn't serve a request: {}", error);
+                }
+            }
+        });
+
+        StubServer { base_url, requests }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Paths with the queries of the requests so far, in the order they came
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// One request per connection, responses close it
+    fn serve(mut stream: TcpStream, routes: &[(String, StubResponse)], requests: &Mutex<Vec<String>>) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Only GET requests without bodies are expected
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+
+        let target = request_line.split(' ').nth(1).unwrap_or_default().to_string();
+        let path = target.split('?').next().unwrap_or_default();
+        requests.lock().unwrap().push(target.clone());
+
+        let not_found = StubResponse::new(404, "text/plain", b"Not found".to_vec());
+        let response = routes
+            .iter()
+            .find(|(route, _)| route == path)
+            .map(|(_, response)| response)
+            .unwrap_or(&not_found);
+
+        write!(
+            stream,
+            "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.content_type,
+            response.body.len()
+        )?;
+        stream.write_all(&response.body)?;
+        stream.flush()
+    }
+}
+
+impl StubResponse {
+    pub fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        StubResponse { status, content_type, body }
+    }
+
+    /// A recorded response in `tests/fixtures`, its content type is told by the extension
+    pub fn fixture(path: &str) -> Self {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", path].iter().collect();
+        let content_type = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => "application/json",
+            Some("html") => "text/html; charset=utf-8",
+            Some("png") => "image/png",
+            _ => "application/octet-stream",
+        };
+
+        Self::new(200, content_type, fs::read(&path).unwrap())
+    }
+
+    fn with_base_url(self, base_url: &str) -> Self {
+        let body = match String::from_utf8(self.body) {
+            Ok(text) => text.replace(BASE_URL_PLACEHOLDER, base_url).into_bytes(),
+            Err(error) => error.into_bytes(),
+        };
+
+        StubResponse { body, ..self }
+    }
+}